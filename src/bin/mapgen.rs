@@ -0,0 +1,182 @@
+use futures::executor::block_on;
+use std::{env, fs, path::Path, process, time::Instant};
+use wgpu_rs::{config, map, noise, settings};
+
+const USAGE: &str =
+    "Usage: mapgen [--size <size>] [--octaves <octaves>] [--output <dir>] [--config <path>] [--software] [--verify-noise] <seed>...";
+
+struct Args {
+    seeds: Vec<String>,
+    size: Option<u32>,
+    octaves: Option<u32>,
+    output: String,
+    config: String,
+    software: bool,
+    verify_noise: bool,
+}
+
+fn main() {
+    env_logger::init();
+    let args = parse_args(env::args().skip(1).collect()).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(1);
+    });
+
+    let mut config = config::WorldConfig::load(&args.config);
+    if let Some(size) = args.size {
+        config.map.tile_size = size;
+        config.map.tile_depth = 0;
+    }
+    if let Some(octaves) = args.octaves {
+        config.map.octaves = octaves;
+    }
+    config.map.cache = false;
+    config.map.chunks = 1;
+    if args.verify_noise {
+        let stages = &mut config.map.stages;
+        stages.erosion = false;
+        stages.thermal = false;
+        stages.smooth = false;
+        stages.water = false;
+        stages.biomes = false;
+        config.map.heightmap = None;
+    }
+    if let Err(e) = config.validate() {
+        eprintln!("Invalid settings: {}", e);
+        process::exit(1);
+    }
+
+    let (device, queue, adapter_info, timestamp_period) = block_on(create_device(args.software));
+    println!(
+        "Adapter: {} ({:?}, {:?})",
+        adapter_info.name, adapter_info.device_type, adapter_info.backend
+    );
+
+    fs::create_dir_all(&args.output).expect("Failed to create output directory");
+    let mut summary = String::from("seed,stage,ms\n");
+
+    for seed in args.seeds.iter() {
+        let start = Instant::now();
+        let mut map_config = config.map.clone();
+        map_config.seed = seed.clone();
+
+        let noise = block_on(noise::Noise::new(&device, &queue, seed));
+        let mut timings = vec![("Noise".to_string(), start.elapsed().as_micros() as f32 / 1000.0)];
+
+        let map = block_on(map::Map::new(&device, &queue, &noise, &map_config, timestamp_period));
+        timings.extend(map.timings.clone());
+
+        if args.verify_noise {
+            let error = noise.max_difference(&map, &map_config);
+            println!("Noise difference for {}: {:.6}", seed, error);
+            if error > noise::ELEVATION_TOLERANCE * map_config.vertical_scale {
+                eprintln!("CPU and GPU noise differ for {}", seed);
+                process::exit(1);
+            }
+        }
+
+        let dir = Path::new(&args.output).join(seed);
+        let dir = dir.to_str().unwrap();
+        let export = Instant::now();
+        if let Err(e) = map.export(dir) {
+            eprintln!("Failed to export {}: {}", seed, e);
+            process::exit(1);
+        }
+        timings.push(("Export".to_string(), export.elapsed().as_micros() as f32 / 1000.0));
+
+        let total = start.elapsed().as_micros() as f32 / 1000.0;
+        let report = serde_json::json!({
+            "seed": seed,
+            "size": map.size,
+            "octaves": map_config.octaves,
+            "adapter": adapter_info.name,
+            "stages": timings.iter().map(|(stage, ms)| serde_json::json!({ "stage": stage, "ms": ms })).collect::<Vec<_>>(),
+            "total_ms": total,
+        });
+        fs::write(
+            Path::new(dir).join("timings.json"),
+            serde_json::to_string_pretty(&report).unwrap(),
+        )
+        .expect("Failed to write timings");
+
+        for (stage, ms) in timings.iter() {
+            summary.push_str(&format!("{},{},{}\n", seed, stage, ms));
+        }
+        summary.push_str(&format!("{},Total,{}\n", seed, total));
+        println!("Generated {}: {} ms", seed, total);
+    }
+
+    fs::write(Path::new(&args.output).join("timings.csv"), summary).expect("Failed to write timings summary");
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        seeds: vec![],
+        size: None,
+        octaves: None,
+        output: "./mapgen".to_string(),
+        config: settings::WORLD_CONFIG_PATH.to_string(),
+        software: false,
+        verify_noise: false,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => parsed.size = Some(parse_number(&arg, args.next())?),
+            "--octaves" => parsed.octaves = Some(parse_number(&arg, args.next())?),
+            "--output" => parsed.output = args.next().ok_or("--output requires a value")?,
+            "--config" => parsed.config = args.next().ok_or("--config requires a value")?,
+            "--software" => parsed.software = true,
+            "--verify-noise" => parsed.verify_noise = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => parsed.seeds.push(arg),
+        }
+    }
+
+    if parsed.seeds.is_empty() {
+        return Err("At least one seed is required".to_string());
+    }
+
+    Ok(parsed)
+}
+
+fn parse_number(arg: &str, value: Option<String>) -> Result<u32, String> {
+    value
+        .and_then(|v| v.parse().ok())
+        .ok_or(format!("{} requires a positive integer", arg))
+}
+
+async fn create_device(software: bool) -> (wgpu::Device, wgpu::Queue, wgpu::AdapterInfo, f32) {
+    let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+
+    // Software rasterizers such as lavapipe or SwiftShader report themselves as CPU devices
+    let adapter = if software {
+        instance
+            .enumerate_adapters(wgpu::BackendBit::VULKAN)
+            .find(|a| a.get_info().device_type == wgpu::DeviceType::Cpu)
+            .expect("No software Vulkan adapter found")
+    } else {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+            })
+            .await
+            .expect("Failed to request adapter")
+    };
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES | (adapter.features() & wgpu::Features::TIMESTAMP_QUERY),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .expect("Failed to request device");
+
+    (device, queue, adapter.get_info(), adapter.get_timestamp_period())
+}
@@ -2,8 +2,6 @@ extern crate cgmath;
 use cgmath::*;
 use std::mem;
 
-use super::bounding_box;
-
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct FrustumCuller {
     f: [Vector4<f32>; 6],
@@ -26,45 +24,18 @@ impl FrustumCuller {
         culler
     }
 
-    pub fn test_bounding_box(&self, aab: &bounding_box::BoundingBox) -> bool {
-        if self.f[0].x * if self.f[0].x < 0.0 { aab.min.x } else { aab.max.x }
-            + self.f[0].y * if self.f[0].y < 0.0 { aab.min.y } else { aab.max.y }
-            + self.f[0].z * if self.f[0].z < 0.0 { aab.min.z } else { aab.max.z }
-            >= -self.f[0].w
-        {
-            if self.f[1].x * if self.f[1].x < 0.0 { aab.min.x } else { aab.max.x }
-                + self.f[1].y * if self.f[1].y < 0.0 { aab.min.y } else { aab.max.y }
-                + self.f[1].z * if self.f[1].z < 0.0 { aab.min.z } else { aab.max.z }
-                >= -self.f[1].w
-            {
-                if self.f[2].x * if self.f[2].x < 0.0 { aab.min.x } else { aab.max.x }
-                    + self.f[2].y * if self.f[2].y < 0.0 { aab.min.y } else { aab.max.y }
-                    + self.f[2].z * if self.f[2].z < 0.0 { aab.min.z } else { aab.max.z }
-                    >= -self.f[2].w
-                {
-                    if self.f[3].x * if self.f[3].x < 0.0 { aab.min.x } else { aab.max.x }
-                        + self.f[3].y * if self.f[3].y < 0.0 { aab.min.y } else { aab.max.y }
-                        + self.f[3].z * if self.f[3].z < 0.0 { aab.min.z } else { aab.max.z }
-                        >= -self.f[3].w
-                    {
-                        if self.f[4].x * if self.f[4].x < 0.0 { aab.min.x } else { aab.max.x }
-                            + self.f[4].y * if self.f[4].y < 0.0 { aab.min.y } else { aab.max.y }
-                            + self.f[4].z * if self.f[4].z < 0.0 { aab.min.z } else { aab.max.z }
-                            >= -self.f[4].w
-                        {
-                            if self.f[5].x * if self.f[5].x < 0.0 { aab.min.x } else { aab.max.x }
-                                + self.f[5].y * if self.f[5].y < 0.0 { aab.min.y } else { aab.max.y }
-                                + self.f[5].z * if self.f[5].z < 0.0 { aab.min.z } else { aab.max.z }
-                                >= -self.f[5].w
-                            {
-                                return true;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    // Drops the near plane by repeating the far one, for depth clamped views where whatever is in front of them still gets drawn
+    pub fn without_near(mut self) -> Self {
+        self.f[4] = self.f[5];
+        self
+    }
 
-        false
+    // The planes with normalized normals so they also give distances, as used by the culling shaders
+    pub fn planes(&self) -> [[f32; 4]; 6] {
+        let mut planes = [[0.0; 4]; 6];
+        for (i, f) in self.f.iter().enumerate() {
+            planes[i] = (f / f.truncate().magnitude()).into();
+        }
+        planes
     }
 }
@@ -1,4 +1,4 @@
-use crate::{input, settings};
+use crate::{config, input, settings};
 use cgmath::*;
 use winit::event::VirtualKeyCode;
 mod bounding_box;
@@ -13,6 +13,7 @@ pub use self::instance::Instance;
 
 pub struct Viewport {
     controller: controller::Controller,
+    camera_config: config::CameraConfig,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub target: Point3<f32>,
     pub eye: Point3<f32>,
@@ -28,7 +29,7 @@ pub struct Viewport {
 }
 
 impl Viewport {
-    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, camera_config: &config::CameraConfig) -> Self {
         let z_near = 1.0;
         let z_far = 1600.0;
         let fov_y = 45.0;
@@ -50,6 +51,7 @@ impl Viewport {
 
         Viewport {
             controller,
+            camera_config: camera_config.clone(),
             target: Point3::new(0.0, 0.0, 0.0),
             eye: Point3::new(0.0, 0.0, 0.0),
             rotation: Point2::new(45.0f32.to_radians(), -90.0f32.to_radians()),
@@ -78,7 +80,7 @@ impl Viewport {
             self.z_far -= 1.0;
         }
 
-        self.controller.process_events(input, frame_time);
+        self.controller.process_events(input, &self.camera_config, frame_time);
 
         self.distance += self.controller.velocity.y;
         self.rotation += self.controller.rotation;
@@ -96,6 +98,16 @@ impl Viewport {
         self.proj = perspective(Deg(self.fov_y), self.width as f32 / self.height as f32, self.z_near, self.z_far);
     }
 
+    // World space ray from the eye through a cursor position given in window pixels
+    pub fn cursor_ray(&self, (x, y): (f32, f32)) -> (Point3<f32>, Vector3<f32>) {
+        let view = Matrix4::look_at_rh(self.eye, self.target, Vector3::unit_y());
+        let inverse = (self.proj * view).invert().unwrap_or(Matrix4::identity());
+        let ndc = vec4(x / self.width as f32 * 2.0 - 1.0, 1.0 - y / self.height as f32 * 2.0, 1.0, 1.0);
+        let far = Point3::from_homogeneous(inverse * ndc);
+
+        (self.eye, (far - self.eye).normalize())
+    }
+
     pub fn create_swap_chain(&self, device: &wgpu::Device, surface: &wgpu::Surface) -> wgpu::SwapChain {
         let swap_chain_desc = wgpu::SwapChainDescriptor {
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
@@ -0,0 +1,634 @@
+use crate::settings;
+use cgmath::*;
+use serde_json::Value;
+use std::{convert::TryFrom, fs};
+
+#[derive(Clone)]
+pub struct MapConfig {
+    pub seed: String,
+    pub tile_size: u32,
+    pub tile_depth: u32,
+    pub octaves: u32,
+    pub sea_level: f32,
+    pub horizontal_scale: f32,
+    pub vertical_scale: f32,
+    pub heightmap: Option<String>,
+    pub cache: bool,
+    pub chunks: u32,
+    pub noise: Vec<NoiseLayerConfig>,
+    pub erosion: ErosionConfig,
+    pub stages: StageConfig,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoiseType {
+    Value,
+    Perlin,
+    Simplex,
+    Worley,
+    Ridged,
+}
+
+// One layer of the elevation noise stack, an empty stack keeps the original domain warped fbm
+#[derive(Clone)]
+pub struct NoiseLayerConfig {
+    pub kind: NoiseType,
+    pub frequency: f32,
+    pub amplitude: f32,
+    pub octaves: u32,
+    pub warp: f32,
+    pub mask: Option<usize>,
+}
+
+#[derive(Clone)]
+pub struct ErosionConfig {
+    pub droplet_steps: u32,
+    pub deposition_rate: f32,
+    pub erosion_rate: f32,
+    pub speed: f32,
+    pub friction: f32,
+    pub thermal_iterations: u32,
+    pub talus_angle: f32,
+    pub thermal_rate: f32,
+}
+
+#[derive(Clone)]
+pub struct StageConfig {
+    pub erosion: bool,
+    pub thermal: bool,
+    pub smooth: bool,
+    pub normals: bool,
+    pub water: bool,
+    pub biomes: bool,
+}
+
+// The colors and strengths are those of noon, dir is only used while the clock is disabled
+#[derive(Clone)]
+pub struct LightConfig {
+    pub dir: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub ambient: f32,
+    pub intensity: f32,
+    pub sky_color: Vector3<f32>,
+    pub sunset_color: Vector3<f32>,
+    pub night_sky_color: Vector3<f32>,
+    pub night_ambient: f32,
+    pub moon_color: Vector3<f32>,
+    pub moon_intensity: f32,
+}
+
+// A day takes day_length seconds, the sun follows its path at an equinox as seen from the latitude in degrees
+#[derive(Clone)]
+pub struct TimeConfig {
+    pub enabled: bool,
+    pub day_length: f32,
+    pub latitude: f32,
+    pub start_hour: f32,
+}
+
+// Manual splits are fractions of the shadow distance, the other schemes only need the cascade count.
+// Cascades with an update interval above one are enlarged by the update margin and only rendered again every interval frames,
+// or earlier when the camera leaves the enlarged cascade or the light turns more than the light threshold in degrees
+#[derive(Clone)]
+pub struct ShadowConfig {
+    pub resolution: u32,
+    pub cascades: u32,
+    pub split_scheme: SplitScheme,
+    pub cascade_splits: Vec<f32>,
+    pub depth_bias: Vec<DepthBiasConfig>,
+    pub blend: f32,
+    pub filter: ShadowFilter,
+    pub filter_radius: f32,
+    pub light_size: f32,
+    pub update_intervals: Vec<u32>,
+    pub update_margin: f32,
+    pub light_threshold: f32,
+}
+
+// Practical splits blend between uniform and logarithmic splits by lambda
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SplitScheme {
+    Manual,
+    Uniform,
+    Logarithmic,
+    Practical(f32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ShadowFilter {
+    Pcf,
+    Poisson,
+    Pcss,
+}
+
+// Depth bias applied while rendering a shadow cascade, wider cascades cover more world per texel and usually need more
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthBiasConfig {
+    pub constant: i32,
+    pub slope_scale: f32,
+}
+
+impl Default for DepthBiasConfig {
+    fn default() -> Self {
+        Self {
+            constant: 2,
+            slope_scale: 2.0,
+        }
+    }
+}
+
+// Loaded nodes are kept until they are hysteresis * z_far further away than where they are loaded,
+// unloaded nodes are cached until the total size of the nodes exceeds the memory budget in megabytes
+#[derive(Clone)]
+pub struct NodeConfig {
+    pub hysteresis: f32,
+    pub memory_budget: u32,
+}
+
+#[derive(Clone)]
+pub struct CameraConfig {
+    pub acceleration: f32,
+    pub friction: f32,
+    pub sensitivity: f32,
+}
+
+#[derive(Clone)]
+pub struct WorldConfig {
+    pub map: MapConfig,
+    pub lods: Vec<f32>,
+    pub light: LightConfig,
+    pub time: TimeConfig,
+    pub shadows: ShadowConfig,
+    pub nodes: NodeConfig,
+    pub camera: CameraConfig,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            map: MapConfig {
+                seed: "RANDOM_SEED".to_string(),
+                tile_size: 80,
+                tile_depth: 5,
+                octaves: 4,
+                sea_level: 0.4,
+                horizontal_scale: 0.005,
+                vertical_scale: 150.0,
+                heightmap: None,
+                cache: true,
+                chunks: 4,
+                noise: vec![],
+                erosion: ErosionConfig {
+                    droplet_steps: 100,
+                    deposition_rate: 0.06,
+                    erosion_rate: 0.08,
+                    speed: 0.15,
+                    friction: 0.7,
+                    thermal_iterations: 32,
+                    talus_angle: 40.0,
+                    thermal_rate: 0.5,
+                },
+                stages: StageConfig {
+                    erosion: true,
+                    thermal: true,
+                    smooth: true,
+                    normals: true,
+                    water: true,
+                    biomes: true,
+                },
+            },
+            lods: vec![0.1, 0.2, 0.3],
+            light: LightConfig {
+                dir: vec3(0.5, -1.0, 0.0),
+                color: vec3(1.0, 0.9, 0.5),
+                ambient: 0.3,
+                intensity: 2.0,
+                sky_color: vec3(0.312, 0.573, 0.757),
+                sunset_color: vec3(1.0, 0.5, 0.25),
+                night_sky_color: vec3(0.02, 0.03, 0.08),
+                night_ambient: 0.1,
+                moon_color: vec3(0.6, 0.7, 1.0),
+                moon_intensity: 0.3,
+            },
+            time: TimeConfig {
+                enabled: true,
+                day_length: 600.0,
+                latitude: 40.0,
+                start_hour: 10.0,
+            },
+            shadows: ShadowConfig {
+                resolution: 4096,
+                cascades: 4,
+                split_scheme: SplitScheme::Manual,
+                cascade_splits: vec![0.05, 0.15, 0.3, 1.0],
+                depth_bias: vec![DepthBiasConfig::default(); 4],
+                blend: 0.1,
+                filter: ShadowFilter::Pcf,
+                filter_radius: 1.2,
+                light_size: 0.02,
+                update_intervals: vec![1, 1, 2, 4],
+                update_margin: 0.1,
+                light_threshold: 0.5,
+            },
+            nodes: NodeConfig {
+                hysteresis: 0.1,
+                memory_budget: 256,
+            },
+            camera: CameraConfig {
+                acceleration: 10.0,
+                friction: 1.0,
+                sensitivity: 10.0,
+            },
+        }
+    }
+}
+
+impl WorldConfig {
+    pub fn load(path: &str) -> Self {
+        match fs::read_to_string(path) {
+            Ok(json) => Self::from_json(&json).unwrap_or_else(|e| panic!("Invalid world config {}: {}", path, e)),
+            Err(_) => {
+                println!("World config {} not found, using defaults", path);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let root: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut config = Self::default();
+
+        let map = &root["map"];
+        config.map.seed = get_string(map, "seed", config.map.seed)?;
+        config.map.tile_size = get_u32(map, "tile_size", config.map.tile_size)?;
+        config.map.tile_depth = get_u32(map, "tile_depth", config.map.tile_depth)?;
+        config.map.octaves = get_u32(map, "octaves", config.map.octaves)?;
+        config.map.sea_level = get_f32(map, "sea_level", config.map.sea_level)?;
+        config.map.horizontal_scale = get_f32(map, "horizontal_scale", config.map.horizontal_scale)?;
+        config.map.vertical_scale = get_f32(map, "vertical_scale", config.map.vertical_scale)?;
+        config.map.heightmap = match &map["heightmap"] {
+            Value::Null => None,
+            _ => Some(get_string(map, "heightmap", String::new())?),
+        };
+        config.map.cache = get_bool(map, "cache", config.map.cache)?;
+        config.map.chunks = get_u32(map, "chunks", config.map.chunks)?;
+
+        config.map.noise = match &map["noise"] {
+            Value::Null => vec![],
+            Value::Array(layers) => layers
+                .iter()
+                .map(|layer| get_noise_layer(layer, config.map.octaves))
+                .collect::<Result<_, _>>()?,
+            _ => return Err("map.noise must be an array of layers".to_string()),
+        };
+
+        let erosion = &map["erosion"];
+        config.map.erosion.droplet_steps = get_u32(erosion, "droplet_steps", config.map.erosion.droplet_steps)?;
+        config.map.erosion.deposition_rate = get_f32(erosion, "deposition_rate", config.map.erosion.deposition_rate)?;
+        config.map.erosion.erosion_rate = get_f32(erosion, "erosion_rate", config.map.erosion.erosion_rate)?;
+        config.map.erosion.speed = get_f32(erosion, "speed", config.map.erosion.speed)?;
+        config.map.erosion.friction = get_f32(erosion, "friction", config.map.erosion.friction)?;
+        config.map.erosion.thermal_iterations = get_u32(erosion, "thermal_iterations", config.map.erosion.thermal_iterations)?;
+        config.map.erosion.talus_angle = get_f32(erosion, "talus_angle", config.map.erosion.talus_angle)?;
+        config.map.erosion.thermal_rate = get_f32(erosion, "thermal_rate", config.map.erosion.thermal_rate)?;
+
+        let stages = &map["stages"];
+        config.map.stages.erosion = get_bool(stages, "erosion", config.map.stages.erosion)?;
+        config.map.stages.thermal = get_bool(stages, "thermal", config.map.stages.thermal)?;
+        config.map.stages.smooth = get_bool(stages, "smooth", config.map.stages.smooth)?;
+        config.map.stages.normals = get_bool(stages, "normals", config.map.stages.normals)?;
+        config.map.stages.water = get_bool(stages, "water", config.map.stages.water)?;
+        config.map.stages.biomes = get_bool(stages, "biomes", config.map.stages.biomes)?;
+
+        config.lods = get_f32_array(&root, "lods", config.lods)?;
+
+        let light = &root["light"];
+        config.light.dir = get_vec3(light, "dir", config.light.dir)?;
+        config.light.color = get_vec3(light, "color", config.light.color)?;
+        config.light.ambient = get_f32(light, "ambient", config.light.ambient)?;
+        config.light.intensity = get_f32(light, "intensity", config.light.intensity)?;
+        config.light.sky_color = get_vec3(light, "sky_color", config.light.sky_color)?;
+        config.light.sunset_color = get_vec3(light, "sunset_color", config.light.sunset_color)?;
+        config.light.night_sky_color = get_vec3(light, "night_sky_color", config.light.night_sky_color)?;
+        config.light.night_ambient = get_f32(light, "night_ambient", config.light.night_ambient)?;
+        config.light.moon_color = get_vec3(light, "moon_color", config.light.moon_color)?;
+        config.light.moon_intensity = get_f32(light, "moon_intensity", config.light.moon_intensity)?;
+
+        let time = &root["time"];
+        config.time.enabled = get_bool(time, "enabled", config.time.enabled)?;
+        config.time.day_length = get_f32(time, "day_length", config.time.day_length)?;
+        config.time.latitude = get_f32(time, "latitude", config.time.latitude)?;
+        config.time.start_hour = get_f32(time, "start_hour", config.time.start_hour)?;
+
+        let shadows = &root["shadows"];
+        config.shadows.resolution = get_u32(shadows, "resolution", config.shadows.resolution)?;
+        config.shadows.cascade_splits = get_f32_array(shadows, "cascade_splits", config.shadows.cascade_splits)?;
+        config.shadows.cascades = get_u32(shadows, "cascades", config.shadows.cascade_splits.len() as u32)?;
+        config.shadows.split_scheme = match get_string(shadows, "split_scheme", "manual".to_string())?.as_str() {
+            "manual" => SplitScheme::Manual,
+            "uniform" => SplitScheme::Uniform,
+            "logarithmic" => SplitScheme::Logarithmic,
+            "practical" => SplitScheme::Practical(get_f32(shadows, "split_lambda", 0.75)?),
+            scheme => return Err(format!("Unknown shadow split scheme {}", scheme)),
+        };
+        config.shadows.depth_bias = match &shadows["depth_bias"] {
+            Value::Null => vec![DepthBiasConfig::default(); config.shadows.cascades as usize],
+            Value::Array(biases) => biases.iter().map(get_depth_bias).collect::<Result<_, _>>()?,
+            _ => return Err("shadows.depth_bias must be an array with one bias per cascade".to_string()),
+        };
+        config.shadows.blend = get_f32(shadows, "blend", config.shadows.blend)?;
+        config.shadows.filter = match get_string(shadows, "filter", "pcf".to_string())?.as_str() {
+            "pcf" => ShadowFilter::Pcf,
+            "poisson" => ShadowFilter::Poisson,
+            "pcss" => ShadowFilter::Pcss,
+            filter => return Err(format!("Unknown shadow filter {}", filter)),
+        };
+        config.shadows.filter_radius = get_f32(shadows, "filter_radius", config.shadows.filter_radius)?;
+        config.shadows.light_size = get_f32(shadows, "light_size", config.shadows.light_size)?;
+        config.shadows.update_intervals = match &shadows["update_intervals"] {
+            Value::Null => (0..config.shadows.cascades).map(|i| 1 << i.saturating_sub(1)).collect(),
+            Value::Array(intervals) => intervals
+                .iter()
+                .map(|v| {
+                    v.as_u64()
+                        .and_then(|v| u32::try_from(v).ok())
+                        .ok_or("update_intervals must only contain positive integers".to_string())
+                })
+                .collect::<Result<_, _>>()?,
+            _ => return Err("shadows.update_intervals must be an array with one interval per cascade".to_string()),
+        };
+        config.shadows.update_margin = get_f32(shadows, "update_margin", config.shadows.update_margin)?;
+        config.shadows.light_threshold = get_f32(shadows, "light_threshold", config.shadows.light_threshold)?;
+
+        let nodes = &root["nodes"];
+        config.nodes.hysteresis = get_f32(nodes, "hysteresis", config.nodes.hysteresis)?;
+        config.nodes.memory_budget = get_u32(nodes, "memory_budget", config.nodes.memory_budget)?;
+
+        let camera = &root["camera"];
+        config.camera.acceleration = get_f32(camera, "acceleration", config.camera.acceleration)?;
+        config.camera.friction = get_f32(camera, "friction", config.camera.friction)?;
+        config.camera.sensitivity = get_f32(camera, "sensitivity", config.camera.sensitivity)?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // Sizes are checked before map_size can be used, large exponents would overflow it
+        let max_lod_step = u32::try_from(self.lods.len())
+            .ok()
+            .and_then(|lods| 2u32.checked_pow(lods))
+            .ok_or("lods has too many levels".to_string())?;
+        if self.map.tile_size == 0 || self.map.tile_size % max_lod_step != 0 {
+            return Err(format!("map.tile_size must be a positive multiple of {}", max_lod_step));
+        }
+        let tiles = 2u32.checked_pow(self.map.tile_depth);
+        let map_size = tiles.and_then(|tiles| self.map.tile_size.checked_mul(tiles));
+        let (tiles, map_size) = match (tiles, map_size) {
+            (Some(tiles), Some(map_size)) if map_size <= settings::MAX_MAP_SIZE => (tiles, map_size),
+            _ => {
+                return Err(format!(
+                    "map.tile_size * 2^map.tile_depth must not exceed {}",
+                    settings::MAX_MAP_SIZE
+                ))
+            }
+        };
+        if !self.map.chunks.is_power_of_two() || self.map.chunks > tiles {
+            return Err("map.chunks must be a power of two no larger than 2^map.tile_depth".to_string());
+        }
+        if (map_size / self.map.chunks) % 16 != 0 {
+            return Err("map.tile_size * 2^map.tile_depth / map.chunks must be a multiple of 16".to_string());
+        }
+        if self.map.octaves == 0 {
+            return Err("map.octaves must be at least 1".to_string());
+        }
+        if self.map.horizontal_scale <= 0.0 || self.map.vertical_scale <= 0.0 {
+            return Err("map.horizontal_scale and map.vertical_scale must be positive".to_string());
+        }
+
+        if self.map.noise.len() > settings::MAX_NOISE_LAYERS {
+            return Err(format!(
+                "map.noise must not have more than {} layers",
+                settings::MAX_NOISE_LAYERS
+            ));
+        }
+        for (i, layer) in self.map.noise.iter().enumerate() {
+            if layer.octaves == 0 || layer.frequency <= 0.0 || layer.warp < 0.0 {
+                return Err("map.noise layers need at least 1 octave, a positive frequency and a non negative warp".to_string());
+            }
+            if layer.mask.map_or(false, |mask| mask >= i) {
+                return Err("map.noise masks must refer to an earlier layer".to_string());
+            }
+        }
+
+        let erosion = &self.map.erosion;
+        if erosion.droplet_steps == 0 || erosion.thermal_iterations == 0 {
+            return Err("map.erosion.droplet_steps and map.erosion.thermal_iterations must be at least 1".to_string());
+        }
+        let rates = [
+            erosion.deposition_rate,
+            erosion.erosion_rate,
+            erosion.friction,
+            erosion.thermal_rate,
+        ];
+        if rates.iter().any(|r| *r < 0.0 || *r > 1.0) || erosion.speed <= 0.0 {
+            return Err("map.erosion rates and friction must be between 0.0 and 1.0 and speed must be positive".to_string());
+        }
+        if erosion.talus_angle <= 0.0 || erosion.talus_angle >= 90.0 {
+            return Err("map.erosion.talus_angle must be between 0 and 90 degrees".to_string());
+        }
+
+        if !is_increasing(&self.lods) {
+            return Err("lods must be increasing values between 0.0 and 1.0".to_string());
+        }
+        if self.lods.len() > settings::MAX_LODS {
+            return Err(format!("lods must not have more than {} entries", settings::MAX_LODS));
+        }
+
+        if self.light.dir.magnitude2() == 0.0 {
+            return Err("light.dir must not be zero".to_string());
+        }
+        if self.light.night_ambient < 0.0 || self.light.moon_intensity < 0.0 {
+            return Err("light.night_ambient and light.moon_intensity must not be negative".to_string());
+        }
+
+        if self.time.day_length <= 0.0 {
+            return Err("time.day_length must be positive".to_string());
+        }
+        if self.time.latitude < -90.0 || self.time.latitude > 90.0 {
+            return Err("time.latitude must be between -90 and 90 degrees".to_string());
+        }
+        if self.time.start_hour < 0.0 || self.time.start_hour >= 24.0 {
+            return Err("time.start_hour must be between 0 and 24".to_string());
+        }
+
+        if self.shadows.resolution == 0 || self.shadows.resolution > settings::MAX_MAP_SIZE {
+            return Err(format!("shadows.resolution must be between 1 and {}", settings::MAX_MAP_SIZE));
+        }
+        if self.shadows.cascades == 0 || self.shadows.cascades as usize > settings::MAX_SHADOW_CASCADES {
+            return Err(format!(
+                "shadows.cascades must be between 1 and {}",
+                settings::MAX_SHADOW_CASCADES
+            ));
+        }
+        if self.shadows.split_scheme == SplitScheme::Manual {
+            if self.shadows.cascade_splits.len() != self.shadows.cascades as usize {
+                return Err("shadows.cascade_splits must have one entry per shadow cascade".to_string());
+            }
+            if !is_increasing(&self.shadows.cascade_splits) {
+                return Err("shadows.cascade_splits must be increasing values between 0.0 and 1.0".to_string());
+            }
+        }
+        if let SplitScheme::Practical(lambda) = self.shadows.split_scheme {
+            if lambda < 0.0 || lambda > 1.0 {
+                return Err("shadows.split_lambda must be between 0.0 and 1.0".to_string());
+            }
+        }
+        if self.shadows.depth_bias.len() != self.shadows.cascades as usize {
+            return Err("shadows.depth_bias must have one entry per shadow cascade".to_string());
+        }
+        if self.shadows.depth_bias.iter().any(|bias| bias.slope_scale < 0.0) {
+            return Err("shadows.depth_bias slope_scale must not be negative".to_string());
+        }
+        if self.shadows.blend < 0.0 || self.shadows.blend > 1.0 {
+            return Err("shadows.blend must be between 0.0 and 1.0".to_string());
+        }
+        if self.shadows.filter_radius < 0.0 || self.shadows.light_size < 0.0 {
+            return Err("shadows.filter_radius and shadows.light_size must not be negative".to_string());
+        }
+        if self.shadows.update_intervals.len() != self.shadows.cascades as usize || self.shadows.update_intervals.contains(&0) {
+            return Err("shadows.update_intervals must have one interval of at least 1 per shadow cascade".to_string());
+        }
+        if self.shadows.update_margin < 0.0 || self.shadows.light_threshold < 0.0 {
+            return Err("shadows.update_margin and shadows.light_threshold must not be negative".to_string());
+        }
+
+        if self.nodes.hysteresis < 0.0 || self.nodes.memory_budget == 0 {
+            return Err("nodes.hysteresis must not be negative and nodes.memory_budget must be at least 1".to_string());
+        }
+
+        if self.camera.acceleration <= 0.0 || self.camera.friction <= 0.0 || self.camera.sensitivity <= 0.0 {
+            return Err("camera values must be positive".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn map_size(&self) -> u32 {
+        self.map.tile_size * 2u32.pow(self.map.tile_depth)
+    }
+}
+
+fn is_increasing(values: &[f32]) -> bool {
+    values.iter().all(|v| *v > 0.0 && *v <= 1.0) && values.windows(2).all(|w| w[0] < w[1])
+}
+
+fn get_noise_layer(layer: &Value, octaves: u32) -> Result<NoiseLayerConfig, String> {
+    let kind = match get_string(layer, "type", "perlin".to_string())?.as_str() {
+        "value" => NoiseType::Value,
+        "perlin" => NoiseType::Perlin,
+        "simplex" => NoiseType::Simplex,
+        "worley" => NoiseType::Worley,
+        "ridged" => NoiseType::Ridged,
+        kind => return Err(format!("Unknown noise type {}", kind)),
+    };
+
+    Ok(NoiseLayerConfig {
+        kind,
+        frequency: get_f32(layer, "frequency", 1.0)?,
+        amplitude: get_f32(layer, "amplitude", 1.0)?,
+        octaves: get_u32(layer, "octaves", octaves)?,
+        warp: get_f32(layer, "warp", 0.0)?,
+        mask: match &layer["mask"] {
+            Value::Null => None,
+            _ => Some(get_u32(layer, "mask", 0)? as usize),
+        },
+    })
+}
+
+fn get_depth_bias(bias: &Value) -> Result<DepthBiasConfig, String> {
+    let default = DepthBiasConfig::default();
+    Ok(DepthBiasConfig {
+        constant: get_i32(bias, "constant", default.constant)?,
+        slope_scale: get_f32(bias, "slope_scale", default.slope_scale)?,
+    })
+}
+
+pub(crate) fn get_f32(parent: &Value, key: &str, default: f32) -> Result<f32, String> {
+    match &parent[key] {
+        Value::Null => Ok(default),
+        _ => require_f32(parent, key),
+    }
+}
+
+fn get_u32(parent: &Value, key: &str, default: u32) -> Result<u32, String> {
+    match &parent[key] {
+        Value::Null => Ok(default),
+        value => value.as_u64().and_then(|v| u32::try_from(v).ok()).ok_or(format!(
+            "{} must be a positive integer no larger than {}",
+            key,
+            u32::MAX
+        )),
+    }
+}
+
+fn get_i32(parent: &Value, key: &str, default: i32) -> Result<i32, String> {
+    match &parent[key] {
+        Value::Null => Ok(default),
+        value => value.as_i64().and_then(|v| i32::try_from(v).ok()).ok_or(format!(
+            "{} must be an integer between {} and {}",
+            key,
+            i32::MIN,
+            i32::MAX
+        )),
+    }
+}
+
+fn get_bool(parent: &Value, key: &str, default: bool) -> Result<bool, String> {
+    match &parent[key] {
+        Value::Null => Ok(default),
+        value => value.as_bool().ok_or(format!("{} must be true or false", key)),
+    }
+}
+
+pub(crate) fn get_string(parent: &Value, key: &str, default: String) -> Result<String, String> {
+    match &parent[key] {
+        Value::Null => Ok(default),
+        _ => require_string(parent, key),
+    }
+}
+
+fn get_f32_array(parent: &Value, key: &str, default: Vec<f32>) -> Result<Vec<f32>, String> {
+    match &parent[key] {
+        Value::Null => Ok(default),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| v.as_f64().map(|v| v as f32).ok_or(format!("{} must only contain numbers", key)))
+            .collect(),
+        _ => Err(format!("{} must be an array", key)),
+    }
+}
+
+fn get_vec3(parent: &Value, key: &str, default: Vector3<f32>) -> Result<Vector3<f32>, String> {
+    let values = get_f32_array(parent, key, vec![default.x, default.y, default.z])?;
+    match values.as_slice() {
+        [x, y, z] => Ok(vec3(*x, *y, *z)),
+        _ => Err(format!("{} must have 3 components", key)),
+    }
+}
+
+// Fields without a default, a missing key is an error
+pub(crate) fn require_f32(parent: &Value, key: &str) -> Result<f32, String> {
+    parent[key].as_f64().map(|v| v as f32).ok_or(format!("{} must be a number", key))
+}
+
+pub(crate) fn require_string(parent: &Value, key: &str) -> Result<String, String> {
+    parent[key]
+        .as_str()
+        .map(|v| v.to_string())
+        .ok_or(format!("{} must be a string", key))
+}
+
+pub(crate) fn require_array<'a>(parent: &'a Value, key: &str) -> Result<&'a Vec<Value>, String> {
+    parent[key].as_array().ok_or(format!("{} must be an array", key))
+}
@@ -6,6 +6,7 @@ pub struct Input {
     pub mouse_buttons: HashSet<MouseButton>,
     pub mouse_delta: (f32, f32),
     pub mouse_scroll_delta: f32,
+    pub cursor_position: (f32, f32),
 }
 
 impl Input {
@@ -15,6 +16,7 @@ impl Input {
             mouse_buttons: HashSet::new(),
             mouse_delta: (0.0, 0.0),
             mouse_scroll_delta: 0.0,
+            cursor_position: (0.0, 0.0),
         }
     }
 
@@ -36,6 +38,10 @@ impl Input {
         }
     }
 
+    pub fn process_cursor_moved(&mut self, position: &PhysicalPosition<f64>) {
+        self.cursor_position = (position.x as f32, position.y as f32);
+    }
+
     pub fn process_device_event(&mut self, event: &DeviceEvent) {
         match event {
             DeviceEvent::MouseMotion { delta: (x, y), .. } => {
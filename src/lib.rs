@@ -0,0 +1,6 @@
+// Map generation, shared by the game and the mapgen tool
+pub mod config;
+pub mod map;
+pub mod noise;
+pub mod settings;
+pub mod texture;
@@ -1,5 +1,5 @@
 use once_cell::sync::Lazy;
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, sync::Mutex, time::Instant};
 
 struct Measurement {
     min: u128,
@@ -10,6 +10,7 @@ struct Measurement {
 
 static APP_START: Lazy<Instant> = Lazy::new(|| Instant::now());
 static mut MEASUREMENTS: Lazy<HashMap<String, Measurement>> = Lazy::new(|| HashMap::default());
+static COUNTERS: Lazy<Mutex<HashMap<String, (u64, u64)>>> = Lazy::new(|| Mutex::new(HashMap::default()));
 
 unsafe fn log(message: String, start: Instant) {
     let elapsed = start.elapsed().as_micros();
@@ -53,7 +54,18 @@ pub fn measure_time<T, F: FnOnce() -> T>(message: &str, f: F) -> T {
     r
 }
 
+// Keeps the latest and the highest value of a counter
+pub fn count(name: &str, value: u64) {
+    let mut counters = COUNTERS.lock().unwrap();
+    let counter = counters.entry(name.to_string()).or_insert((0, 0));
+    *counter = (value, counter.1.max(value));
+}
+
 pub fn print() {
+    for (name, (current, max)) in COUNTERS.lock().unwrap().iter() {
+        println!("{}: current: {} - max: {}", name, current, max);
+    }
+
     unsafe {
         for (message, m) in MEASUREMENTS.iter() {
             println!(
@@ -11,14 +11,12 @@ mod camera;
 mod input;
 mod logger;
 mod model;
-mod noise;
 mod pipelines;
 mod plane;
-mod settings;
 mod state;
-mod texture;
 mod world;
 pub use state::*;
+use wgpu_rs::{config, map, noise, settings, texture};
 
 fn exit(control_flow: &mut ControlFlow) {
     logger::print();
@@ -27,12 +25,15 @@ fn exit(control_flow: &mut ControlFlow) {
 
 fn main() {
     env_logger::init();
+    let config_path = std::env::args().nth(1).unwrap_or(settings::WORLD_CONFIG_PATH.to_string());
+    let config = config::WorldConfig::load(&config_path);
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("WGPU-RS")
         .build(&event_loop)
         .expect("Failed to create window!");
-    let mut state = block_on(state::State::new(&window));
+    let mut state = block_on(state::State::new(&window, config, &config_path));
     let mut fps = 0;
     let mut last_update = Instant::now();
     let mut profiling = false;
@@ -75,6 +76,12 @@ fn main() {
                 ..
             } => {
                 &state.input.process_mouse_button(button, mouse_state);
+                if *button == MouseButton::Left && *mouse_state == ElementState::Pressed {
+                    state.pick();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                state.input.process_cursor_moved(position);
             }
             WindowEvent::KeyboardInput { input, .. } => match input {
                 KeyboardInput {
@@ -113,6 +120,69 @@ fn main() {
                 } => {
                     state.anti_aliasing.toggle();
                 }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::M),
+                    ..
+                } => {
+                    state.export_map();
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::B),
+                    ..
+                } => {
+                    state.toggle_sculpting();
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::R),
+                    ..
+                } => {
+                    state.regenerate();
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::H),
+                    ..
+                } => {
+                    state.reload_shadows();
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::T),
+                    ..
+                } => {
+                    state.toggle_time();
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Equals),
+                    ..
+                } => {
+                    state.set_time_speed(2.0);
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Minus),
+                    ..
+                } => {
+                    state.set_time_speed(0.5);
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Z),
+                    ..
+                } => {
+                    state.undo();
+                }
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::Y),
+                    ..
+                } => {
+                    state.redo();
+                }
                 input => {
                     &state.input.process_key(input);
                 }
@@ -0,0 +1,217 @@
+use crate::config::{require_array, require_f32, require_string};
+use serde_json::Value;
+use std::fs;
+
+// Order matches the texture array bound to the terrain shader, each texture is followed by its normal map
+pub const TEXTURES: [&str; 13] = [
+    "desert",
+    "grassland",
+    "snow",
+    "tropical",
+    "tundra",
+    "barren",
+    "rock",
+    "cliff",
+    "sand",
+    "flowers",
+    "mud",
+    "pebbles_grass",
+    "pebbles_desert",
+];
+
+// Matches MAX_BIOME_RANGES in terrain.frag
+pub const MAX_BIOME_RANGES: usize = 8;
+
+#[derive(Clone)]
+pub struct Biome {
+    pub name: String,
+    texture: usize,
+    alt: [usize; 2],
+    cliff: usize,
+    beach: usize,
+}
+
+// Temperatures up to max_temp blend from the dry to the wet biome around moist_split
+#[derive(Clone)]
+pub struct BiomeRange {
+    pub max_temp: f32,
+    pub moist_split: f32,
+    pub dry: usize,
+    pub wet: usize,
+}
+
+#[derive(Clone)]
+pub struct Biomes {
+    pub biomes: Vec<Biome>,
+    pub ranges: Vec<BiomeRange>,
+    pub temp_overlap: f32,
+    pub moist_overlap: f32,
+}
+
+pub struct BiomeSample {
+    pub biome: usize,
+    pub weights: Vec<(usize, f32)>,
+    pub temperature: f32,
+    pub moisture: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RangeUniforms {
+    dry: [i32; 4],
+    wet: [i32; 4],
+    beach: [i32; 2],
+    max_temp: f32,
+    moist_split: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uniforms {
+    ranges: [RangeUniforms; MAX_BIOME_RANGES],
+    range_count: u32,
+    temp_overlap: f32,
+    moist_overlap: f32,
+    padding: u32,
+}
+
+impl Biomes {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let root: Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+        let biomes = require_array(&root, "biomes")?
+            .iter()
+            .map(|b| {
+                let alt = require_array(b, "alt")?;
+                if alt.len() != 2 {
+                    return Err("alt must have 2 textures".to_string());
+                }
+                Ok(Biome {
+                    name: require_string(b, "name")?,
+                    texture: get_texture(b, "texture")?,
+                    alt: [to_texture(&alt[0])?, to_texture(&alt[1])?],
+                    cliff: get_texture(b, "cliff")?,
+                    beach: get_texture(b, "beach")?,
+                })
+            })
+            .collect::<Result<Vec<Biome>, String>>()?;
+
+        let find = |parent: &Value, key: &str| {
+            let name = require_string(parent, key)?;
+            biomes.iter().position(|b| b.name == name).ok_or(format!("Unknown biome {}", name))
+        };
+        let ranges = require_array(&root, "ranges")?
+            .iter()
+            .map(|r| {
+                Ok(BiomeRange {
+                    max_temp: require_f32(r, "max_temp")?,
+                    moist_split: require_f32(r, "moist_split")?,
+                    dry: find(r, "dry")?,
+                    wet: find(r, "wet")?,
+                })
+            })
+            .collect::<Result<Vec<BiomeRange>, String>>()?;
+
+        if ranges.is_empty() || ranges.len() > MAX_BIOME_RANGES {
+            return Err(format!("ranges must have 1 to {} entries", MAX_BIOME_RANGES));
+        }
+        if ranges.windows(2).any(|w| w[0].max_temp >= w[1].max_temp) {
+            return Err("ranges must be sorted by increasing max_temp".to_string());
+        }
+
+        Ok(Self {
+            biomes,
+            ranges,
+            temp_overlap: require_f32(&root, "temp_overlap")?,
+            moist_overlap: require_f32(&root, "moist_overlap")?,
+        })
+    }
+
+    // Same blending as terrain.frag so the dominant biome matches what is rendered
+    pub fn resolve(&self, temperature: f32, moisture: f32) -> BiomeSample {
+        let mut weights = vec![0.0; self.biomes.len()];
+        weights[self.ranges[0].wet] = 1.0;
+
+        let mut min_temp = self.ranges[0].max_temp;
+        for range in self.ranges.iter().skip(1) {
+            if temperature >= min_temp - self.temp_overlap && temperature <= range.max_temp {
+                let wet = ((moisture - range.moist_split) / self.moist_overlap * 0.5 + 0.5).max(0.0).min(1.0);
+                let mut current = vec![0.0; self.biomes.len()];
+                current[range.dry] += 1.0 - wet;
+                current[range.wet] += wet;
+
+                let temp_diff = (min_temp - temperature) / self.temp_overlap;
+                let previous = if temp_diff > 0.0 && temp_diff < 1.0 { temp_diff } else { 0.0 };
+                weights = weights
+                    .iter()
+                    .zip(current)
+                    .map(|(w, c)| w * previous + c * (1.0 - previous))
+                    .collect();
+            }
+
+            min_temp = range.max_temp;
+        }
+
+        let biome = (0..weights.len())
+            .max_by(|a, b| weights[*a].partial_cmp(&weights[*b]).unwrap())
+            .unwrap();
+
+        BiomeSample {
+            biome,
+            weights: weights.into_iter().enumerate().filter(|(_, w)| *w > 0.0).collect(),
+            temperature,
+            moisture,
+        }
+    }
+
+    pub fn get_name(&self, biome: usize) -> &str {
+        &self.biomes[biome].name
+    }
+
+    pub fn get_uniforms(&self) -> Uniforms {
+        let textures = |b: &Biome| {
+            [
+                b.texture as i32 * 2,
+                b.alt[0] as i32 * 2,
+                b.alt[1] as i32 * 2,
+                b.cliff as i32 * 2,
+            ]
+        };
+        let mut ranges = [RangeUniforms {
+            dry: [0; 4],
+            wet: [0; 4],
+            beach: [0; 2],
+            max_temp: 0.0,
+            moist_split: 0.0,
+        }; MAX_BIOME_RANGES];
+
+        for (uniforms, range) in ranges.iter_mut().zip(self.ranges.iter()) {
+            let (dry, wet) = (&self.biomes[range.dry], &self.biomes[range.wet]);
+            *uniforms = RangeUniforms {
+                dry: textures(dry),
+                wet: textures(wet),
+                beach: [dry.beach as i32 * 2, wet.beach as i32 * 2],
+                max_temp: range.max_temp,
+                moist_split: range.moist_split,
+            };
+        }
+
+        Uniforms {
+            ranges,
+            range_count: self.ranges.len() as u32,
+            temp_overlap: self.temp_overlap,
+            moist_overlap: self.moist_overlap,
+            padding: 0,
+        }
+    }
+}
+
+fn get_texture(parent: &Value, key: &str) -> Result<usize, String> {
+    to_texture(&parent[key])
+}
+
+fn to_texture(value: &Value) -> Result<usize, String> {
+    let name = value.as_str().ok_or("Textures must be given by name")?;
+    TEXTURES.iter().position(|t| *t == name).ok_or(format!("Unknown texture {}", name))
+}
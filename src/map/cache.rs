@@ -0,0 +1,197 @@
+use super::{RawData, EROSION_APRON, EROSION_STAGES};
+use crate::config;
+use cgmath::*;
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+const CACHE_DIR: &str = "./cache";
+const MAGIC: &[u8; 8] = b"WGPU_MAP";
+const VERSION: u32 = 3;
+
+// Part of the key so a changed generation stage never loads terrain generated by the old one
+const GENERATION_SHADERS: [&[u8]; 10] = [
+    include_bytes!("../shaders/compiled/terrain-elev.comp.spv"),
+    include_bytes!("../shaders/compiled/terrain-norm.comp.spv"),
+    include_bytes!("../shaders/compiled/terrain-erosion.comp.spv"),
+    include_bytes!("../shaders/compiled/terrain-thermal.comp.spv"),
+    include_bytes!("../shaders/compiled/terrain-smooth.comp.spv"),
+    include_bytes!("../shaders/compiled/water-lakes.comp.spv"),
+    include_bytes!("../shaders/compiled/water-rivers.comp.spv"),
+    include_bytes!("../shaders/compiled/biome-temperature.comp.spv"),
+    include_bytes!("../shaders/compiled/biome-moisture.comp.spv"),
+    include_bytes!("../shaders/compiled/biome-decoration.comp.spv"),
+];
+
+pub fn get_key(map_config: &config::MapConfig) -> u64 {
+    let mut hash = Fnv::new();
+    for shader in GENERATION_SHADERS.iter() {
+        hash.write(shader);
+    }
+    hash.write(map_config.seed.as_bytes());
+    hash.write(&map_config.tile_size.to_le_bytes());
+    hash.write(&map_config.tile_depth.to_le_bytes());
+    hash.write(&map_config.octaves.to_le_bytes());
+    hash.write(&map_config.sea_level.to_le_bytes());
+    hash.write(&map_config.horizontal_scale.to_le_bytes());
+    hash.write(&map_config.vertical_scale.to_le_bytes());
+    // Chunks set the regions the stages run on, which also decides the lake and river iterations
+    hash.write(&map_config.chunks.to_le_bytes());
+    hash.write(&EROSION_STAGES.to_le_bytes());
+    hash.write(&EROSION_APRON.to_le_bytes());
+
+    for layer in map_config.noise.iter() {
+        hash.write(&[layer.kind as u8]);
+        hash.write(&layer.frequency.to_le_bytes());
+        hash.write(&layer.amplitude.to_le_bytes());
+        hash.write(&layer.octaves.to_le_bytes());
+        hash.write(&layer.warp.to_le_bytes());
+        hash.write(&layer.mask.map_or(-1, |mask| mask as i32).to_le_bytes());
+    }
+
+    let erosion = &map_config.erosion;
+    hash.write(&erosion.droplet_steps.to_le_bytes());
+    hash.write(&erosion.deposition_rate.to_le_bytes());
+    hash.write(&erosion.erosion_rate.to_le_bytes());
+    hash.write(&erosion.speed.to_le_bytes());
+    hash.write(&erosion.friction.to_le_bytes());
+    hash.write(&erosion.thermal_iterations.to_le_bytes());
+    hash.write(&erosion.talus_angle.to_le_bytes());
+    hash.write(&erosion.thermal_rate.to_le_bytes());
+
+    let stages = &map_config.stages;
+    hash.write(&[
+        stages.erosion as u8,
+        stages.thermal as u8,
+        stages.smooth as u8,
+        stages.normals as u8,
+        stages.water as u8,
+        stages.biomes as u8,
+    ]);
+
+    // Imported heightmaps are keyed by path and modification time rather than by hashing their content
+    if let Some(path) = &map_config.heightmap {
+        hash.write(path.as_bytes());
+        if let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) {
+            let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+            hash.write(&since_epoch.as_nanos().to_le_bytes());
+        }
+    }
+
+    hash.finish()
+}
+
+pub fn load(key: u64, size: u32) -> io::Result<RawData> {
+    let mut file = io::BufReader::new(fs::File::open(get_path(key))?);
+
+    let mut magic = [0u8; 8];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a map cache file"));
+    }
+    if read_u32(&mut file)? != VERSION {
+        return Err(invalid_data("Unsupported map cache version"));
+    }
+    if read_u64(&mut file)? != key || read_u32(&mut file)? != size {
+        return Err(invalid_data("Map cache does not match settings"));
+    }
+
+    let elevation_normals = read_layer(&mut file, size)?;
+    let biome = read_layer(&mut file, size)?;
+    let water = read_layer(&mut file, size)?;
+    Ok(RawData {
+        elevation_normals,
+        biome,
+        water,
+    })
+}
+
+pub fn save(key: u64, size: u32, data: &RawData) -> io::Result<()> {
+    fs::create_dir_all(CACHE_DIR)?;
+
+    // Write to a temporary file first so an interrupted save never leaves a truncated cache behind
+    let path = get_path(key);
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        file.write_all(&key.to_le_bytes())?;
+        file.write_all(&size.to_le_bytes())?;
+        write_layer(&mut file, &data.elevation_normals)?;
+        write_layer(&mut file, &data.biome)?;
+        write_layer(&mut file, &data.water)?;
+        file.flush()?;
+    }
+
+    fs::rename(tmp_path, path)
+}
+
+fn get_path(key: u64) -> PathBuf {
+    PathBuf::from(CACHE_DIR).join(format!("map_{:016x}.bin", key))
+}
+
+pub fn read_layer(file: &mut impl Read, size: u32) -> io::Result<Vec<Vector4<f32>>> {
+    let mut bytes = vec![0u8; (size * size) as usize * 16];
+    file.read_exact(&mut bytes)?;
+
+    Ok(bytes
+        .chunks_exact(16)
+        .map(|t| {
+            vec4(
+                f32::from_le_bytes([t[0], t[1], t[2], t[3]]),
+                f32::from_le_bytes([t[4], t[5], t[6], t[7]]),
+                f32::from_le_bytes([t[8], t[9], t[10], t[11]]),
+                f32::from_le_bytes([t[12], t[13], t[14], t[15]]),
+            )
+        })
+        .collect())
+}
+
+pub fn write_layer(file: &mut impl Write, layer: &[Vector4<f32>]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(layer.len() * 16);
+    for t in layer {
+        for v in [t.x, t.y, t.z, t.w].iter() {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    file.write_all(&bytes)
+}
+
+fn read_u32(file: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(file: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// FNV-1a, used instead of DefaultHasher since the key has to stay stable between builds
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
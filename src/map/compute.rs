@@ -0,0 +1,359 @@
+use super::{task, Task};
+use super::{textures, uniforms};
+use crate::{config, noise, texture};
+use cgmath::*;
+use std::{num::NonZeroU64, time::Instant};
+
+// Matches local_size_x in compute.glsl
+const WORKGROUP_SIZE: u32 = 16;
+
+pub struct Compute {
+    pub elevation_pipeline: wgpu::ComputePipeline,
+    pub normal_pipeline: wgpu::ComputePipeline,
+    pub erosion_pipeline: wgpu::ComputePipeline,
+    pub thermal_pipeline: wgpu::ComputePipeline,
+    pub smooth_pipeline: wgpu::ComputePipeline,
+    pub lakes_pipeline: wgpu::ComputePipeline,
+    pub rivers_pipeline: wgpu::ComputePipeline,
+    pub temperature_pipeline: wgpu::ComputePipeline,
+    pub moisture_pipeline: wgpu::ComputePipeline,
+    pub decoration_pipeline: wgpu::ComputePipeline,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    noise_stack_buffer: wgpu::Buffer,
+    noise_bindings: noise::NoiseBindings,
+    compute_texture_bind_group: wgpu::BindGroup,
+    timestamp_period: Option<f32>,
+    size: u32,
+}
+
+impl Compute {
+    pub fn new(device: &wgpu::Device, noise: &noise::Noise, textures: &textures::Textures, size: u32, timestamp_period: f32) -> Self {
+        let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("uniform_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: NonZeroU64::new(uniforms::UNIFORMS_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // Rewritten on every run since regenerating a region can change the noise stack
+        let noise_stack_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("noise_stack_buffer"),
+            size: std::mem::size_of::<uniforms::NoiseStackUniforms>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        format: wgpu::TextureFormat::Rgba32Float,
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        format: wgpu::TextureFormat::Rgba32Float,
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        format: wgpu::TextureFormat::Rgba32Float,
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        format: wgpu::TextureFormat::R32Float,
+                        access: wgpu::StorageTextureAccess::ReadWrite,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let compute_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("compute_textures"),
+            layout: &texture_bind_group_layout,
+            entries: &[
+                texture::create_bind_group_entry(0, &textures.elevation_normal),
+                texture::create_bind_group_entry(1, &textures.biome),
+                texture::create_bind_group_entry(2, &textures.water),
+                texture::create_bind_group_entry(3, &textures.scratch),
+            ],
+        });
+
+        let noise_bindings = noise.create_bindings(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("terrain_compute_layout"),
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &texture_bind_group_layout,
+                &noise_bindings.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // Terrain pipelines
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/terrain-elev.comp.spv"));
+        let elevation_pipeline = create_pipeline(device, &layout, &module, "elevation");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/terrain-norm.comp.spv"));
+        let normal_pipeline = create_pipeline(device, &layout, &module, "normal");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/terrain-erosion.comp.spv"));
+        let erosion_pipeline = create_pipeline(device, &layout, &module, "erosion");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/terrain-thermal.comp.spv"));
+        let thermal_pipeline = create_pipeline(device, &layout, &module, "thermal");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/terrain-smooth.comp.spv"));
+        let smooth_pipeline = create_pipeline(device, &layout, &module, "smooth");
+
+        // Water pipelines
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/water-lakes.comp.spv"));
+        let lakes_pipeline = create_pipeline(device, &layout, &module, "lakes");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/water-rivers.comp.spv"));
+        let rivers_pipeline = create_pipeline(device, &layout, &module, "rivers");
+
+        // Biome pipelines
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/biome-temperature.comp.spv"));
+        let temperature_pipeline = create_pipeline(device, &layout, &module, "temperature");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/biome-moisture.comp.spv"));
+        let moisture_pipeline = create_pipeline(device, &layout, &module, "moisture");
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../shaders/compiled/biome-decoration.comp.spv"));
+        let decoration_pipeline = create_pipeline(device, &layout, &module, "decoration");
+
+        Self {
+            elevation_pipeline,
+            normal_pipeline,
+            erosion_pipeline,
+            thermal_pipeline,
+            smooth_pipeline,
+            lakes_pipeline,
+            rivers_pipeline,
+            temperature_pipeline,
+            moisture_pipeline,
+            decoration_pipeline,
+            uniform_bind_group_layout,
+            noise_stack_buffer,
+            noise_bindings,
+            compute_texture_bind_group,
+            timestamp_period: match device.features().contains(wgpu::Features::TIMESTAMP_QUERY) && timestamp_period > 0.0 {
+                true => Some(timestamp_period),
+                false => None,
+            },
+            size,
+        }
+    }
+
+    pub fn run<'a>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        map_config: &config::MapConfig,
+        tasks: Vec<&'a task::Task<'a>>,
+    ) -> Vec<(String, f32)> {
+        let now = Instant::now();
+        let tasks = task::sort(tasks);
+        let uniforms = self.create_uniforms(device, queue, map_config, &tasks);
+
+        let query_count = tasks.len() as u32 * 2;
+        let timestamps = self.timestamp_period.map(|period| {
+            (
+                period,
+                device.create_query_set(&wgpu::QuerySetDescriptor {
+                    ty: wgpu::QueryType::Timestamp,
+                    count: query_count,
+                }),
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("compute_timestamps"),
+                    size: (query_count as usize * std::mem::size_of::<u64>()) as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+            )
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("compute_tasks"),
+        });
+        self.encode(
+            &mut encoder,
+            &tasks,
+            &uniforms,
+            timestamps.as_ref().map(|(_, query_set, _)| query_set),
+        );
+        if let Some((_, query_set, buffer)) = &timestamps {
+            encoder.resolve_query_set(query_set, 0..query_count, buffer, 0);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+
+        let timings = match &timestamps {
+            Some((period, _, buffer)) => {
+                let buffer_slice = buffer.slice(..);
+                let mapping = buffer_slice.map_async(wgpu::MapMode::Read);
+                device.poll(wgpu::Maintain::Wait);
+                futures::executor::block_on(mapping).unwrap();
+
+                let data = buffer_slice.get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                tasks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, task)| {
+                        let elapsed = ticks[i * 2 + 1].wrapping_sub(ticks[i * 2]) as f64 * *period as f64 / 1_000_000.0;
+                        (task.label.clone(), elapsed as f32)
+                    })
+                    .collect()
+            }
+            // Without timestamp queries only the whole graph can be timed without stalling between tasks
+            None => vec![("Compute".to_string(), now.elapsed().as_micros() as f32 / 1000.0)],
+        };
+
+        timings.iter().for_each(|(label, elapsed)| println!("{}: {} ms", label, elapsed));
+        timings
+    }
+
+    // Queues the tasks without waiting for them, so their results can be picked up on a later frame
+    pub fn submit<'a>(&self, device: &wgpu::Device, queue: &wgpu::Queue, map_config: &config::MapConfig, tasks: Vec<&'a task::Task<'a>>) {
+        let tasks = task::sort(tasks);
+        let uniforms = self.create_uniforms(device, queue, map_config, &tasks);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("compute_tasks"),
+        });
+        self.encode(&mut encoder, &tasks, &uniforms, None);
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    fn create_uniforms(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        map_config: &config::MapConfig,
+        tasks: &[&task::Task],
+    ) -> uniforms::UniformBuffer {
+        let uniforms = uniforms::UniformBuffer::new(
+            device,
+            &self.uniform_bind_group_layout,
+            tasks
+                .iter()
+                .flat_map(|task| {
+                    let (origin, extent) = task.region.unwrap_or((vec2(0, 0), vec2(self.size, self.size)));
+                    (0..task.stage_count).map(move |i| uniforms::Uniforms {
+                        origin: origin.into(),
+                        extent: extent.into(),
+                        size: self.size,
+                        octaves: map_config.octaves,
+                        sea_level: map_config.sea_level,
+                        horizontal_scale: map_config.horizontal_scale,
+                        vertical_scale: map_config.vertical_scale,
+                        current_stage: i,
+                        stage_count: task.stage_count,
+                        droplet_steps: map_config.erosion.droplet_steps,
+                        deposition_rate: map_config.erosion.deposition_rate,
+                        erosion_rate: map_config.erosion.erosion_rate,
+                        erosion_speed: map_config.erosion.speed,
+                        erosion_friction: map_config.erosion.friction,
+                        talus: map_config.erosion.talus_angle.to_radians().tan(),
+                        thermal_rate: map_config.erosion.thermal_rate,
+                        apron: task.apron,
+                    })
+                })
+                .collect(),
+            &self.noise_stack_buffer,
+        );
+        queue.write_buffer(
+            &self.noise_stack_buffer,
+            0,
+            bytemuck::bytes_of(&uniforms::NoiseStackUniforms::new(&map_config.noise)),
+        );
+        uniforms
+    }
+
+    fn encode(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        tasks: &[&task::Task],
+        uniforms: &uniforms::UniformBuffer,
+        query_set: Option<&wgpu::QuerySet>,
+    ) {
+        let mut offset = 0;
+        for (i, task) in tasks.iter().enumerate() {
+            if let Some(query_set) = query_set {
+                encoder.write_timestamp(query_set, i as u32 * 2);
+            }
+            for _ in 0..task.run_times {
+                for stage in 0..task.stage_count as usize {
+                    self.run_task(encoder, &task, &uniforms, offset + stage);
+                }
+            }
+            if let Some(query_set) = query_set {
+                encoder.write_timestamp(query_set, i as u32 * 2 + 1);
+            }
+            offset += task.stage_count as usize;
+        }
+    }
+
+    fn run_task(&self, encoder: &mut wgpu::CommandEncoder, task: &Task, uniforms: &uniforms::UniformBuffer, index: usize) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(format!("compute_{}", task.label.to_lowercase()).as_str()),
+        });
+        pass.set_pipeline(&task.pipeline);
+        pass.set_bind_group(0, &uniforms.bind_group, &[uniforms.offset(index)]);
+        pass.set_bind_group(1, &self.compute_texture_bind_group, &[]);
+        pass.set_bind_group(2, &self.noise_bindings.bind_group, &[]);
+        let (_, extent) = task.region.unwrap_or((vec2(0, 0), vec2(self.size, self.size)));
+        pass.dispatch((extent.x + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, extent.y, 1);
+    }
+}
+
+fn create_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule, name: &str) -> wgpu::ComputePipeline {
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(format!("compute_{}_pipeline", name).as_str()),
+        layout: Some(&layout),
+        module,
+        entry_point: "main",
+    })
+}
@@ -0,0 +1,98 @@
+use super::{cache, RawData};
+use cgmath::*;
+use image::{GrayImage, ImageBuffer, Luma, RgbImage};
+use std::{fs, io, path::Path};
+
+pub fn export(data: &RawData, size: u32, dir: &str) -> Result<(), String> {
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let elevation: Vec<f32> = data.elevation_normals.iter().map(|v| v.x).collect();
+    let (elevation_min, elevation_max) = min_max(&elevation);
+    save_gray16(dir, "heightmap.png", size, &elevation, elevation_min, elevation_max)?;
+
+    // Stored as R = x, G = z, B = y so that up facing normals look like a regular normal map
+    let normals = data
+        .elevation_normals
+        .iter()
+        .flat_map(|v| vec![to_u8(v.y, -1.0, 1.0), to_u8(v.w, -1.0, 1.0), to_u8(v.z, -1.0, 1.0)])
+        .collect();
+    RgbImage::from_raw(size, size, normals)
+        .unwrap()
+        .save(dir.join("normals.png"))
+        .map_err(|e| e.to_string())?;
+
+    let temperature: Vec<f32> = data.biome.iter().map(|b| b.x).collect();
+    let (temperature_min, temperature_max) = min_max(&temperature);
+    save_gray(dir, "temperature.png", size, &temperature, temperature_min, temperature_max)?;
+    save_gray(dir, "moisture.png", size, &get_channel(&data.biome, 1), 0.0, 1.0)?;
+    save_gray(dir, "cliff_beach.png", size, &get_channel(&data.biome, 2), -1.0, 1.0)?;
+    save_gray(dir, "decoration.png", size, &get_channel(&data.biome, 3), -1.0, 1.0)?;
+
+    // Water heights share the elevation range, dry texels are left at the bottom of it
+    let water_mask: Vec<f32> = data.water.iter().map(|w| (w.y > 0.0) as u8 as f32).collect();
+    let water_height: Vec<f32> = data.water.iter().map(|w| if w.y > 0.0 { w.x } else { elevation_min }).collect();
+    save_gray(dir, "water_mask.png", size, &water_mask, 0.0, 1.0)?;
+    save_gray16(dir, "water_height.png", size, &water_height, elevation_min, elevation_max)?;
+
+    save_raw(dir, "elevation_normals.raw", &data.elevation_normals).map_err(|e| e.to_string())?;
+    save_raw(dir, "biome.raw", &data.biome).map_err(|e| e.to_string())?;
+    save_raw(dir, "water.raw", &data.water).map_err(|e| e.to_string())?;
+
+    // Ranges needed to map the normalized images back to world values
+    let metadata = serde_json::json!({
+        "size": size,
+        "elevation": [elevation_min, elevation_max],
+        "temperature": [temperature_min, temperature_max],
+        "moisture": [0.0, 1.0],
+        "cliff_beach": [-1.0, 1.0],
+        "decoration": [-1.0, 1.0],
+        "water_height": [elevation_min, elevation_max],
+        "raw_layout": "rgba32f little endian, elevation_normals = (elevation, normal.xyz), biome = (temperature, moisture, cliff_beach, decoration), water = (surface height, depth, flow accumulation, flow direction)",
+    });
+    fs::write(dir.join("metadata.json"), serde_json::to_string_pretty(&metadata).unwrap()).map_err(|e| e.to_string())
+}
+
+fn save_gray(dir: &Path, name: &str, size: u32, values: &[f32], min: f32, max: f32) -> Result<(), String> {
+    let pixels = values.iter().map(|v| to_u8(*v, min, max)).collect();
+    GrayImage::from_raw(size, size, pixels)
+        .unwrap()
+        .save(dir.join(name))
+        .map_err(|e| e.to_string())
+}
+
+fn save_gray16(dir: &Path, name: &str, size: u32, values: &[f32], min: f32, max: f32) -> Result<(), String> {
+    let pixels = values
+        .iter()
+        .map(|v| (normalize(*v, min, max) * u16::MAX as f32).round() as u16)
+        .collect();
+    ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(size, size, pixels)
+        .unwrap()
+        .save(dir.join(name))
+        .map_err(|e| e.to_string())
+}
+
+fn save_raw(dir: &Path, name: &str, layer: &[Vector4<f32>]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(dir.join(name))?);
+    cache::write_layer(&mut file, layer)
+}
+
+fn get_channel(layer: &[Vector4<f32>], channel: usize) -> Vec<f32> {
+    layer.iter().map(|v| v[channel]).collect()
+}
+
+fn min_max(values: &[f32]) -> (f32, f32) {
+    values.iter().fold((f32::MAX, f32::MIN), |(min, max), v| (min.min(*v), max.max(*v)))
+}
+
+fn normalize(value: f32, min: f32, max: f32) -> f32 {
+    if max > min {
+        ((value - min) / (max - min)).max(0.0).min(1.0)
+    } else {
+        0.0
+    }
+}
+
+fn to_u8(value: f32, min: f32, max: f32) -> u8 {
+    (normalize(value, min, max) * 255.0).round() as u8
+}
@@ -0,0 +1,100 @@
+use cgmath::*;
+use std::{fs, path::Path};
+
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Heightmap {
+    // Heights are normalized to 0.0 - 1.0 for integer formats, float formats (r32, exr) are used as is
+    pub fn load(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .ok_or(format!("Heightmap {} has no extension", path))?;
+
+        match extension.as_str() {
+            "png" => load_png(path),
+            "r16" | "raw" => load_raw(path, 2, |b| u16::from_le_bytes([b[0], b[1]]) as f32 / u16::MAX as f32),
+            "r32" => load_raw(path, 4, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            "exr" => load_exr(path),
+            _ => Err(format!("Unsupported heightmap format: {}", extension)),
+        }
+    }
+
+    // Matches the output of terrain-elev.comp: elevation in x, up facing normal in yzw
+    pub fn to_elevation(&self, size: u32, sea_level: f32, vertical_scale: f32) -> Vec<Vector4<f32>> {
+        let mut elevation = Vec::with_capacity((size * size) as usize);
+        let scale_x = (self.width - 1) as f32 / (size - 1).max(1) as f32;
+        let scale_z = (self.height - 1) as f32 / (size - 1).max(1) as f32;
+
+        for z in 0..size {
+            for x in 0..size {
+                let h = self.sample(x as f32 * scale_x, z as f32 * scale_z);
+                elevation.push(vec4((h - sea_level) * vertical_scale, 0.0, 1.0, 0.0));
+            }
+        }
+
+        elevation
+    }
+
+    fn sample(&self, x: f32, z: f32) -> f32 {
+        let (x0, z0) = (x.floor() as usize, z.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(self.width - 1), (z0 + 1).min(self.height - 1));
+        let (fx, fz) = (x.fract(), z.fract());
+
+        let top = self.get(x0, z0) * (1.0 - fx) + self.get(x1, z0) * fx;
+        let bottom = self.get(x0, z1) * (1.0 - fx) + self.get(x1, z1) * fx;
+        top * (1.0 - fz) + bottom * fz
+    }
+
+    fn get(&self, x: usize, z: usize) -> f32 {
+        self.data[z * self.width + x]
+    }
+}
+
+fn load_png(path: &str) -> Result<Heightmap, String> {
+    let image = image::open(path).map_err(|e| e.to_string())?.into_luma16();
+    let (width, height) = image.dimensions();
+    let data = image.into_raw().iter().map(|v| *v as f32 / u16::MAX as f32).collect();
+
+    validate(width as usize, height as usize, data)
+}
+
+fn load_raw(path: &str, bytes_per_sample: usize, parse: fn(&[u8]) -> f32) -> Result<Heightmap, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let samples = bytes.len() / bytes_per_sample;
+
+    // Raw files carry no header, so only square heightmaps are supported
+    let size = (samples as f64).sqrt() as usize;
+    if size * size * bytes_per_sample != bytes.len() {
+        return Err(format!("Raw heightmap {} is not square", path));
+    }
+
+    validate(size, size, bytes.chunks_exact(bytes_per_sample).map(parse).collect())
+}
+
+fn load_exr(path: &str) -> Result<Heightmap, String> {
+    let image = exr::prelude::read_first_flat_layer_from_file(path).map_err(|e| e.to_string())?;
+    let layer = image.layer_data;
+    let channels = &layer.channel_data.list;
+
+    let channel = channels
+        .iter()
+        .find(|c| c.name.eq("Y") || c.name.eq("R"))
+        .or(channels.first())
+        .ok_or(format!("Heightmap {} has no channels", path))?;
+
+    validate(layer.size.0, layer.size.1, channel.sample_data.values_as_f32().collect())
+}
+
+fn validate(width: usize, height: usize, data: Vec<f32>) -> Result<Heightmap, String> {
+    if width < 2 || height < 2 {
+        return Err(format!("Heightmap must be at least 2x2, got {}x{}", width, height));
+    }
+
+    Ok(Heightmap { width, height, data })
+}
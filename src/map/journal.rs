@@ -0,0 +1,195 @@
+use cgmath::*;
+use serde_json::{json, Value};
+use std::{collections::HashMap, fs, ops, path::Path};
+
+const VERSION: u64 = 3;
+
+// The editable values of a texel, temperature and moisture are the inputs of the biome lookup.
+// The journal holds them as differences from the generated terrain, strokes as the values themselves.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Texel {
+    pub elevation: f32,
+    pub temperature: f32,
+    pub moisture: f32,
+}
+
+impl Texel {
+    fn is_zero(&self) -> bool {
+        self.elevation.abs() < 0.0001 && self.temperature.abs() < 0.0001 && self.moisture.abs() < 0.0001
+    }
+}
+
+impl ops::Add for Texel {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            elevation: self.elevation + other.elevation,
+            temperature: self.temperature + other.temperature,
+            moisture: self.moisture + other.moisture,
+        }
+    }
+}
+
+impl ops::Sub for Texel {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            elevation: self.elevation - other.elevation,
+            temperature: self.temperature - other.temperature,
+            moisture: self.moisture - other.moisture,
+        }
+    }
+}
+
+// Texels changed by one stroke with their values before and after it
+struct Stroke {
+    changes: Vec<((i32, i32), Texel, Texel)>,
+}
+
+// Edits are kept as sparse differences from the generated terrain so they can be replayed whenever a chunk is generated
+pub struct Journal {
+    path: String,
+    seed: String,
+    pub diffs: HashMap<(i32, i32), Texel>,
+    stroke: HashMap<(i32, i32), (Texel, Texel)>,
+    undo: Vec<Stroke>,
+    redo: Vec<Stroke>,
+}
+
+impl Journal {
+    pub fn load(dir: &str, seed: &str) -> Result<Self, String> {
+        let path = Path::new(dir).join(format!("{}.json", seed)).to_str().unwrap().to_string();
+        let mut journal = Self {
+            path,
+            seed: seed.to_string(),
+            diffs: HashMap::new(),
+            stroke: HashMap::new(),
+            undo: vec![],
+            redo: vec![],
+        };
+
+        let json = match fs::read_to_string(&journal.path) {
+            Ok(json) => json,
+            Err(_) => return Ok(journal),
+        };
+        let root: Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        // Version 2 files only hold elevation edits
+        let version = root["version"].as_u64();
+        if version != Some(2) && version != Some(VERSION) {
+            return Err("Unsupported edit file version".to_string());
+        }
+        if root["seed"].as_str() != Some(seed) {
+            return Err("Edit file belongs to another seed".to_string());
+        }
+
+        for edit in root["edits"].as_array().ok_or("edits must be an array")? {
+            let values: Vec<f64> = edit
+                .as_array()
+                .and_then(|v| v.iter().map(|v| v.as_f64()).collect())
+                .ok_or("edits must only contain arrays of numbers")?;
+            match values.as_slice() {
+                [x, z, elevation] if version == Some(2) => {
+                    journal.diffs.insert(
+                        (*x as i32, *z as i32),
+                        Texel {
+                            elevation: *elevation as f32,
+                            ..Texel::default()
+                        },
+                    );
+                }
+                [x, z, elevation, temperature, moisture] if version == Some(VERSION) => {
+                    journal.diffs.insert(
+                        (*x as i32, *z as i32),
+                        Texel {
+                            elevation: *elevation as f32,
+                            temperature: *temperature as f32,
+                            moisture: *moisture as f32,
+                        },
+                    );
+                }
+                _ => return Err("edits must be [x, z, elevation, temperature, moisture]".to_string()),
+            }
+        }
+
+        Ok(journal)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = Path::new(&self.path).parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+
+        // Sorted so that saving the same edits always gives the same file
+        let mut edits: Vec<(&(i32, i32), &Texel)> = self.diffs.iter().collect();
+        edits.sort_by_key(|((x, z), _)| (*z, *x));
+        let edits: Vec<Value> = edits
+            .iter()
+            .map(|((x, z), d)| json!([x, z, round(d.elevation), round(d.temperature), round(d.moisture)]))
+            .collect();
+
+        let root = json!({ "version": VERSION, "seed": self.seed, "edits": edits });
+        fs::write(&self.path, serde_json::to_string(&root).unwrap()).map_err(|e| e.to_string())
+    }
+
+    // Diffs inside a rectangle of world texels, min inclusive and max exclusive
+    pub fn get_diffs(&self, min: Vector2<i32>, max: Vector2<i32>) -> Vec<((i32, i32), Texel)> {
+        self.diffs
+            .iter()
+            .filter(|((x, z), _)| *x >= min.x && *z >= min.y && *x < max.x && *z < max.y)
+            .map(|(p, d)| (*p, *d))
+            .collect()
+    }
+
+    pub fn record(&mut self, p: (i32, i32), before: Texel, after: Texel) {
+        self.stroke.entry(p).or_insert((before, after)).1 = after;
+        self.add_diff(p, after - before);
+    }
+
+    pub fn end_stroke(&mut self) -> bool {
+        if self.stroke.is_empty() {
+            return false;
+        }
+
+        let changes = self.stroke.drain().map(|(p, (before, after))| (p, before, after)).collect();
+        self.undo.push(Stroke { changes });
+        self.redo.clear();
+        true
+    }
+
+    // Returns the texels and the values they should be restored to
+    pub fn undo(&mut self) -> Option<Vec<((i32, i32), Texel)>> {
+        let stroke = self.undo.pop()?;
+        for (p, before, after) in stroke.changes.iter() {
+            self.add_diff(*p, *before - *after);
+        }
+
+        let changes = stroke.changes.iter().map(|(p, before, _)| (*p, *before)).collect();
+        self.redo.push(stroke);
+        Some(changes)
+    }
+
+    pub fn redo(&mut self) -> Option<Vec<((i32, i32), Texel)>> {
+        let stroke = self.redo.pop()?;
+        for (p, before, after) in stroke.changes.iter() {
+            self.add_diff(*p, *after - *before);
+        }
+
+        let changes = stroke.changes.iter().map(|(p, _, after)| (*p, *after)).collect();
+        self.undo.push(stroke);
+        Some(changes)
+    }
+
+    fn add_diff(&mut self, p: (i32, i32), delta: Texel) {
+        let diff = self.diffs.entry(p).or_default();
+        *diff = *diff + delta;
+        if diff.is_zero() {
+            self.diffs.remove(&p);
+        }
+    }
+}
+
+fn round(value: f32) -> f64 {
+    (value as f64 * 1000.0).round() / 1000.0
+}
@@ -0,0 +1,756 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock, RwLockReadGuard},
+    time::Instant,
+    usize,
+};
+
+pub use self::biomes::{BiomeSample, Biomes};
+pub use self::task::Task;
+use crate::{config, noise, settings};
+use cgmath::*;
+pub mod biomes;
+mod cache;
+mod compute;
+mod export;
+mod heightmap;
+mod journal;
+mod raycast;
+mod sampling;
+pub mod sculpt;
+mod task;
+mod textures;
+mod uniforms;
+
+const REGENERATE_MARGIN: u32 = 16;
+// Copying in, two rounds over the droplet groups of terrain-erosion.comp and copying back out
+const EROSION_STAGES: u32 = 1 + 2 * 4 + 1;
+// Droplets start up to 60 texels from their seed and flow about 20 further
+const EROSION_APRON: u32 = 96;
+
+pub struct Map {
+    pub compute: compute::Compute,
+    pub textures: textures::Textures,
+    pub heightfield: Arc<RwLock<Heightfield>>,
+    pub size: u32,
+    pub timings: Vec<(String, f32)>,
+    pub chunk_size: u32,
+    map_config: config::MapConfig,
+    pending: Vec<(i32, i32)>,
+    generating: Option<ChunkJob>,
+    journal: journal::Journal,
+}
+
+// A chunk submitted to the GPU, its readbacks are picked up on a later frame
+struct ChunkJob {
+    chunk: (i32, i32),
+    start: Instant,
+    readbacks: Vec<RegionReadback>,
+}
+
+// The map textures of one wrapped part of a region on their way to the CPU
+struct RegionReadback {
+    origin: Vector2<u32>,
+    extent: Vector2<u32>,
+    elevation_normals: textures::Readback,
+    biome: textures::Readback,
+    water: textures::Readback,
+}
+
+impl RegionReadback {
+    fn is_ready(&mut self) -> bool {
+        // Every readback is polled so all of them are picked up on the same frame
+        let ready = [self.elevation_normals.is_ready(), self.biome.is_ready(), self.water.is_ready()];
+        ready.iter().all(|ready| *ready)
+    }
+}
+
+// The generated data and the window of chunks it covers, shared with the workers that build quadtree leaves
+pub struct Heightfield {
+    pub biomes: Biomes,
+    pub size: u32,
+    pub chunk_size: u32,
+    origin: Vector2<i32>,
+    chunks: HashSet<(i32, i32)>,
+    // Texel rectangle held by a copy made with copy_window, the full map wraps around instead
+    window: Option<(Vector2<i32>, Vector2<i32>)>,
+    data: RawData,
+}
+
+struct RawData {
+    pub elevation_normals: Vec<Vector4<f32>>,
+    pub biome: Vec<Vector4<f32>>,
+    pub water: Vec<Vector4<f32>>,
+}
+
+impl Map {
+    pub async fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        noise: &noise::Noise,
+        map_config: &config::MapConfig,
+        timestamp_period: f32,
+    ) -> Self {
+        let start = Instant::now();
+
+        let size = map_config.tile_size * 2u32.pow(map_config.tile_depth);
+        let chunk_size = size / map_config.chunks;
+        let textures = textures::Textures::new(device, size);
+        let compute = compute::Compute::new(device, noise, &textures, size, timestamp_period);
+        let biomes = Biomes::load(settings::BIOMES_PATH).unwrap_or_else(|e| panic!("Invalid biomes {}: {}", settings::BIOMES_PATH, e));
+
+        let journal = journal::Journal::load(settings::EDITS_PATH, &map_config.seed)
+            .unwrap_or_else(|e| panic!("Invalid edits in {}: {}", settings::EDITS_PATH, e));
+
+        let cache_key = cache::get_key(map_config);
+        let cached = match map_config.cache {
+            true => cache::load(cache_key, size).map_err(|e| println!("Map cache miss: {}", e)).ok(),
+            false => None,
+        };
+
+        let (data, timings) = match cached {
+            Some(data) => {
+                textures.write_texture(queue, &textures.elevation_normal_texture, &data.elevation_normals, size);
+                textures.write_texture(queue, &textures.biome_texture, &data.biome, size);
+                textures.write_texture(queue, &textures.water_texture, &data.water, size);
+                println!("Load cache: {} ms", start.elapsed().as_millis());
+                (
+                    data,
+                    vec![("Load cache".to_string(), start.elapsed().as_micros() as f32 / 1000.0)],
+                )
+            }
+            None => {
+                let (data, timings) = generate(device, queue, &compute, &textures, map_config, size).await;
+                if map_config.cache {
+                    if let Err(error) = cache::save(cache_key, size, &data) {
+                        println!("Failed to save map cache: {}", error);
+                    }
+                }
+                (data, timings)
+            }
+        };
+
+        let count = map_config.chunks as i32;
+        let chunks = (0..count).flat_map(|z| (0..count).map(move |x| (x, z))).collect();
+        let heightfield = Heightfield {
+            biomes,
+            size,
+            chunk_size,
+            origin: vec2(0, 0),
+            chunks,
+            window: None,
+            data,
+        };
+
+        let mut map = Self {
+            size,
+            textures,
+            compute,
+            timings,
+            chunk_size,
+            heightfield: Arc::new(RwLock::new(heightfield)),
+            map_config: map_config.clone(),
+            pending: vec![],
+            generating: None,
+            journal,
+        };
+
+        // Edits are replayed after the cache is saved so the cache always holds the seeded terrain
+        let half_size = size as i32 / 2;
+        map.apply_edits(queue, vec2(-half_size, -half_size), vec2(half_size, half_size));
+
+        println!("Build map: {} ms", start.elapsed().as_millis());
+        map
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Heightfield> {
+        self.heightfield.read().unwrap()
+    }
+
+    // Keeps the window of chunks centered on the camera, returns true when the window has moved
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, eye: Point3<f32>) -> bool {
+        if self.map_config.chunks == 1 || self.map_config.heightmap.is_some() {
+            return false;
+        }
+
+        let count = self.map_config.chunks as i32;
+        let half_size = self.size as f32 / 2.0;
+        let target = vec2(eye.x + half_size, eye.z + half_size) / self.chunk_size as f32 - vec2(count as f32, count as f32) / 2.0;
+
+        // Hysteresis so that moving back and forth over a chunk border does not regenerate chunks
+        let current = self.read().origin;
+        let moved = (target.x - current.x as f32).abs() > 0.75 || (target.y - current.y as f32).abs() > 0.75;
+        if moved {
+            let origin = vec2(target.x.round() as i32, target.y.round() as i32);
+            let in_window = |chunk: &(i32, i32)| is_in_window(origin, count, *chunk);
+            let generating = self.generating.as_ref().map(|job| job.chunk);
+
+            let mut heightfield = self.heightfield.write().unwrap();
+            heightfield.origin = origin;
+            heightfield.chunks.retain(in_window);
+            self.pending.retain(in_window);
+
+            for z in origin.y..origin.y + count {
+                for x in origin.x..origin.x + count {
+                    if !heightfield.chunks.contains(&(x, z)) && !self.pending.contains(&(x, z)) && generating != Some((x, z)) {
+                        self.pending.push((x, z));
+                    }
+                }
+            }
+
+            // Chunks closest to the camera are popped first
+            let center = target + vec2(count as f32, count as f32) / 2.0 - vec2(0.5, 0.5);
+            self.pending.sort_by(|a, b| {
+                let da = vec2(a.0 as f32, a.1 as f32).distance2(center);
+                let db = vec2(b.0 as f32, b.1 as f32).distance2(center);
+                db.partial_cmp(&da).unwrap()
+            });
+        }
+
+        self.finish_chunk(device, queue);
+        if self.generating.is_none() {
+            if let Some(chunk) = self.pending.pop() {
+                self.generating = Some(self.start_chunk(device, queue, chunk));
+            }
+        }
+
+        moved
+    }
+
+    fn start_chunk(&self, device: &wgpu::Device, queue: &wgpu::Queue, (cx, cz): (i32, i32)) -> ChunkJob {
+        let start = Instant::now();
+        let size = self.chunk_size as i32;
+        let region = (vec2(cx, cz) * size, vec2(size as u32, size as u32));
+
+        // Smoothing and normals reach one texel into generated neighbours so both sides of the border match
+        let border = {
+            let heightfield = self.read();
+            let grow = |x: i32, z: i32| heightfield.chunks.contains(&(cx + x, cz + z)) as i32;
+            (
+                region.0 - vec2(grow(-1, 0), grow(0, -1)),
+                vec2(
+                    (size + grow(-1, 0) + grow(1, 0)) as u32,
+                    (size + grow(0, -1) + grow(0, 1)) as u32,
+                ),
+            )
+        };
+
+        let tasks = create_tasks(&self.compute, &self.map_config, true, region, border);
+        self.compute.submit(device, queue, &self.map_config, tasks.iter().collect());
+        ChunkJob {
+            chunk: (cx, cz),
+            start,
+            readbacks: self.start_read_region(device, queue, border),
+        }
+    }
+
+    // Stores the chunk being generated once its readbacks have arrived, without waiting for the GPU
+    fn finish_chunk(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        match &mut self.generating {
+            Some(job) => {
+                device.poll(wgpu::Maintain::Poll);
+                if !job.readbacks.iter_mut().all(|readback| readback.is_ready()) {
+                    return;
+                }
+            }
+            None => return,
+        }
+
+        let job = self.generating.take().unwrap();
+        let (cx, cz) = job.chunk;
+
+        // The window may have moved past the chunk while it was generated, its texels then belong to another chunk
+        if !is_in_window(self.read().origin, self.map_config.chunks as i32, job.chunk) {
+            return;
+        }
+
+        self.store_region(job.readbacks);
+        self.heightfield.write().unwrap().chunks.insert(job.chunk);
+
+        let size = self.chunk_size as i32;
+        let half_size = self.size as i32 / 2;
+        let min = vec2(cx, cz) * size - vec2(half_size, half_size);
+        self.apply_edits(queue, min, min + vec2(size, size));
+        println!("Generate chunk {}, {}: {} ms", cx, cz, job.start.elapsed().as_millis());
+    }
+
+    // Reruns the generation stages on a world space rectangle, the margin around it is blended with the previous terrain
+    pub async fn regenerate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        map_config: &config::MapConfig,
+        min: Vector2<f32>,
+        max: Vector2<f32>,
+    ) -> Result<(Vector2<f32>, Vector2<f32>), String> {
+        let current = &self.map_config;
+        if map_config.heightmap.is_some() || current.heightmap.is_some() {
+            return Err("imported heightmaps can not be regenerated".to_string());
+        }
+        if map_config.seed != current.seed
+            || map_config.tile_size != current.tile_size
+            || map_config.tile_depth != current.tile_depth
+            || map_config.chunks != current.chunks
+        {
+            return Err("changing the seed, size or chunks requires a restart".to_string());
+        }
+
+        let start = Instant::now();
+        self.map_config = map_config.clone();
+
+        let half_size = self.size as i32 / 2;
+        let margin = REGENERATE_MARGIN as i32;
+        let inner = (
+            vec2(min.x.floor() as i32, min.y.floor() as i32),
+            vec2(max.x.ceil() as i32, max.y.ceil() as i32),
+        );
+        let origin = inner.0 - vec2(margin, margin);
+        let extent = vec2(
+            (inner.1.x - origin.x + margin + 1).min(self.size as i32) as u32,
+            (inner.1.y - origin.y + margin + 1).min(self.size as i32) as u32,
+        );
+        let (min, max) = (origin, origin + vec2(extent.x as i32, extent.y as i32));
+
+        // Keep the previous terrain of the margin so it can be blended with the new one
+        let mut previous = vec![];
+        let heightfield = self.read();
+        for z in min.y..max.y {
+            for x in min.x..max.x {
+                let distance = (inner.0.x - x).max(x - inner.1.x).max(inner.0.y - z).max(z - inner.1.y);
+                if distance <= 0 {
+                    continue;
+                }
+                if let Some(index) = heightfield.get_index(vec2(x as f32, z as f32)) {
+                    let t = 1.0 - distance as f32 / margin as f32;
+                    let weight = t * t * (3.0 - 2.0 * t);
+                    previous.push((
+                        index,
+                        weight,
+                        heightfield.data.elevation_normals[index],
+                        heightfield.data.biome[index],
+                        heightfield.data.water[index],
+                    ));
+                }
+            }
+        }
+        drop(heightfield);
+
+        let region = (min + vec2(half_size, half_size), extent);
+        let tasks = create_tasks(&self.compute, &self.map_config, true, region, region);
+        self.compute.run(device, queue, &self.map_config, tasks.iter().collect());
+        self.read_region(device, queue, region).await;
+        self.apply_edits(queue, min, max);
+
+        let mut heightfield = self.heightfield.write().unwrap();
+        for (index, weight, elevation_normal, biome, water) in previous {
+            let data = &mut heightfield.data;
+            let blended = elevation_normal.lerp(data.elevation_normals[index], weight);
+            let normal = vec3(blended.y, blended.z, blended.w).normalize();
+            data.elevation_normals[index] = vec4(blended.x, normal.x, normal.y, normal.z);
+            data.biome[index] = biome.lerp(data.biome[index], weight);
+
+            // Flow directions can not be interpolated
+            if weight < 0.5 {
+                data.water[index] = water;
+            }
+        }
+
+        let (min, max) = (vec2(min.x as f32, min.y as f32), vec2((max.x - 1) as f32, (max.y - 1) as f32));
+        heightfield.upload_region(queue, &self.textures, min, max);
+        println!("Regenerate region: {} ms", start.elapsed().as_millis());
+        Ok((min, max))
+    }
+
+    async fn read_region(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, region: (Vector2<i32>, Vector2<u32>)) {
+        let mut readbacks = self.start_read_region(device, queue, region);
+        device.poll(wgpu::Maintain::Wait);
+        for readback in readbacks.iter_mut() {
+            readback.elevation_normals.wait().await;
+            readback.biome.wait().await;
+            readback.water.wait().await;
+        }
+        self.store_region(readbacks);
+    }
+
+    // Starts reading back a region in unwrapped map coordinates, readbacks have to start and end on a multiple of 16 texels
+    fn start_read_region(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (origin, extent): (Vector2<i32>, Vector2<u32>),
+    ) -> Vec<RegionReadback> {
+        let min = vec2(origin.x.div_euclid(16) * 16, origin.y);
+        let max = vec2(
+            (origin.x + extent.x as i32 + 15).div_euclid(16) * 16,
+            origin.y + extent.y as i32,
+        );
+        let regions = self.read().wrap_region(min, max - min);
+        regions
+            .into_iter()
+            .map(|(origin, extent)| RegionReadback {
+                origin,
+                extent,
+                elevation_normals: self
+                    .textures
+                    .start_read(device, queue, &self.textures.elevation_normal_texture, origin, extent),
+                biome: self
+                    .textures
+                    .start_read(device, queue, &self.textures.biome_texture, origin, extent),
+                water: self
+                    .textures
+                    .start_read(device, queue, &self.textures.water_texture, origin, extent),
+            })
+            .collect()
+    }
+
+    fn store_region(&self, readbacks: Vec<RegionReadback>) {
+        let mut heightfield = self.heightfield.write().unwrap();
+        for readback in readbacks {
+            let (origin, extent) = (readback.origin, readback.extent);
+            let elevation_normals = readback.elevation_normals.data();
+            let biome = readback.biome.data();
+            let water = readback.water.data();
+            for z in 0..extent.y {
+                for x in 0..extent.x {
+                    let from = (z * extent.x + x) as usize;
+                    let to = ((origin.y + z) * self.size + origin.x + x) as usize;
+                    heightfield.data.elevation_normals[to] = elevation_normals[from];
+                    heightfield.data.biome[to] = biome[from];
+                    heightfield.data.water[to] = water[from];
+                }
+            }
+        }
+    }
+
+    pub fn get_center(&self) -> Vector2<f32> {
+        self.read().get_center()
+    }
+
+    pub fn export(&self, dir: &str) -> Result<(), String> {
+        let start = Instant::now();
+        let heightfield = self.read();
+
+        // Unwrap the window so the exported images start at its first chunk
+        let size = self.size as i32;
+        let origin = heightfield.origin * self.chunk_size as i32;
+        let indices: Vec<usize> = (0..size)
+            .flat_map(|z| (0..size).map(move |x| ((origin.y + z).rem_euclid(size) * size + (origin.x + x).rem_euclid(size)) as usize))
+            .collect();
+        let data = RawData {
+            elevation_normals: indices.iter().map(|i| heightfield.data.elevation_normals[*i]).collect(),
+            biome: indices.iter().map(|i| heightfield.data.biome[*i]).collect(),
+            water: indices.iter().map(|i| heightfield.data.water[*i]).collect(),
+        };
+
+        export::export(&data, self.size, dir)?;
+        println!("Export map: {} ms", start.elapsed().as_millis());
+        Ok(())
+    }
+}
+
+impl Heightfield {
+    // Uploads an inclusive world space rectangle of the raw data to the textures
+    fn upload_region(&self, queue: &wgpu::Queue, textures: &textures::Textures, min: Vector2<f32>, max: Vector2<f32>) {
+        let half_size = self.size as i32 / 2;
+        let origin = vec2(min.x as i32 + half_size, min.y as i32 + half_size);
+        let extent = vec2((max.x - min.x) as i32 + 1, (max.y - min.y) as i32 + 1);
+
+        for (origin, extent) in self.wrap_region(origin, extent) {
+            let indices: Vec<usize> = (0..extent.y)
+                .flat_map(|z| (0..extent.x).map(move |x| ((origin.y + z) * self.size + origin.x + x) as usize))
+                .collect();
+            let elevation_normals: Vec<_> = indices.iter().map(|i| self.data.elevation_normals[*i]).collect();
+            let biome: Vec<_> = indices.iter().map(|i| self.data.biome[*i]).collect();
+            let water: Vec<_> = indices.iter().map(|i| self.data.water[*i]).collect();
+            textures.write_region(queue, &textures.elevation_normal_texture, &elevation_normals, origin, extent);
+            textures.write_region(queue, &textures.biome_texture, &biome, origin, extent);
+            textures.write_region(queue, &textures.water_texture, &water, origin, extent);
+        }
+    }
+
+    // Splits a region in unwrapped map coordinates into the texture rectangles it covers
+    fn wrap_region(&self, origin: Vector2<i32>, extent: Vector2<i32>) -> Vec<(Vector2<u32>, Vector2<u32>)> {
+        let size = self.size as i32;
+        let split = |start: i32, length: i32| {
+            let start = start.rem_euclid(size);
+            if start + length <= size {
+                vec![(start as u32, length as u32)]
+            } else {
+                vec![(start as u32, (size - start) as u32), (0, (length - size + start) as u32)]
+            }
+        };
+
+        split(origin.y, extent.y)
+            .into_iter()
+            .flat_map(|(z, height)| {
+                split(origin.x, extent.x)
+                    .into_iter()
+                    .map(move |(x, width)| (vec2(x, z), vec2(width, height)))
+            })
+            .collect()
+    }
+
+    pub fn get_center(&self) -> Vector2<f32> {
+        vec2(self.origin.x as f32, self.origin.y as f32) * self.chunk_size as f32
+    }
+
+    pub fn is_generated(&self, p: Vector2<f32>) -> bool {
+        self.get_index(p).is_some()
+    }
+
+    // Copies the texels of a world space rectangle so they can be sampled without holding the lock on the map
+    pub fn copy_window(&self, min: Vector2<f32>, max: Vector2<f32>) -> Heightfield {
+        let half_size = self.size as f32 / 2.0;
+        let origin = vec2((min.x + half_size).floor() as i32, (min.y + half_size).floor() as i32);
+        let extent = vec2(
+            (max.x + half_size).floor() as i32 - origin.x + 1,
+            (max.y + half_size).floor() as i32 - origin.y + 1,
+        );
+
+        let indices: Vec<Option<usize>> = (origin.y..origin.y + extent.y)
+            .flat_map(|z| (origin.x..origin.x + extent.x).map(move |x| (x, z)))
+            .map(|(x, z)| self.get_texel_index(x, z))
+            .collect();
+        let copy = |layer: &Vec<Vector4<f32>>| indices.iter().map(|i| i.map_or(Vector4::zero(), |i| layer[i])).collect();
+
+        Heightfield {
+            biomes: self.biomes.clone(),
+            size: self.size,
+            chunk_size: self.chunk_size,
+            origin: self.origin,
+            chunks: self.chunks.clone(),
+            window: Some((origin, extent)),
+            data: RawData {
+                elevation_normals: copy(&self.data.elevation_normals),
+                biome: copy(&self.data.biome),
+                water: copy(&self.data.water),
+            },
+        }
+    }
+
+    fn get_index(&self, p: Vector2<f32>) -> Option<usize> {
+        let half_size = self.size as f32 / 2.0;
+        self.get_texel_index((p.x + half_size).floor() as i32, (p.y + half_size).floor() as i32)
+    }
+
+    fn get_texel_index(&self, x: i32, z: i32) -> Option<usize> {
+        let chunk_size = self.chunk_size as i32;
+        if !self.chunks.contains(&(x.div_euclid(chunk_size), z.div_euclid(chunk_size))) {
+            return None;
+        }
+
+        match self.window {
+            Some((origin, extent)) => {
+                let (x, z) = (x - origin.x, z - origin.y);
+                if x < 0 || z < 0 || x >= extent.x || z >= extent.y {
+                    return None;
+                }
+                Some((z * extent.x + x) as usize)
+            }
+            None => {
+                let size = self.size as i32;
+                Some((z.rem_euclid(size) * size + x.rem_euclid(size)) as usize)
+            }
+        }
+    }
+
+    pub fn min_max_elevation(&self, cx: f32, cz: f32, size: u32) -> (f32, f32) {
+        let size = size as i32;
+        let x = cx as i32 - size / 2;
+        let z = cz as i32 - size / 2;
+
+        let mut y_min = f32::MAX;
+        let mut y_max = f32::MIN;
+
+        for z in z..=(z + size) {
+            for x in x..=(x + size) {
+                if let Some((pos, _)) = self.get_position_normal(vec2(x as f32, z as f32)) {
+                    y_min = y_min.min(pos.y);
+                    y_max = y_max.max(pos.y);
+                }
+            }
+        }
+
+        match y_min <= y_max {
+            true => (y_min, y_max),
+            false => (0.0, 0.0),
+        }
+    }
+
+    // Lowest water surface within a node, sea level counts wherever the terrain is below it
+    pub fn min_water_level(&self, cx: f32, cz: f32, size: u32) -> Option<f32> {
+        let size = size as i32;
+        let x = cx as i32 - size / 2;
+        let z = cz as i32 - size / 2;
+        let mut level: Option<f32> = None;
+
+        for z in z..=(z + size) {
+            for x in x..=(x + size) {
+                let p = vec2(x as f32, z as f32);
+                let water = match self.get_water(p) {
+                    Some(height) => Some(height),
+                    None if self.get_position_normal(p).map_or(false, |(pos, _)| pos.y < 0.0) => Some(0.0),
+                    None => None,
+                };
+                if let Some(water) = water {
+                    level = Some(level.map_or(water, |l| l.min(water)));
+                }
+            }
+        }
+
+        level
+    }
+}
+
+async fn generate(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    compute: &compute::Compute,
+    textures: &textures::Textures,
+    map_config: &config::MapConfig,
+    size: u32,
+) -> (RawData, Vec<(String, f32)>) {
+    let mut timings = vec![];
+
+    // Terrain
+    if let Some(path) = &map_config.heightmap {
+        let now = Instant::now();
+        let heightmap = heightmap::Heightmap::load(path).unwrap_or_else(|e| panic!("Failed to import heightmap {}: {}", path, e));
+        let elevation = heightmap.to_elevation(size, map_config.sea_level, map_config.vertical_scale);
+        textures.write_texture(queue, &textures.elevation_normal_texture, &elevation, size);
+        println!("Import heightmap: {} ms", now.elapsed().as_millis());
+        timings.push(("Import heightmap".to_string(), now.elapsed().as_micros() as f32 / 1000.0));
+    }
+
+    let region = (vec2(0, 0), vec2(size, size));
+    let tasks = create_tasks(compute, map_config, map_config.heightmap.is_none(), region, region);
+    timings.extend(compute.run(device, queue, map_config, tasks.iter().collect()));
+
+    let parse = Instant::now();
+    let elevation_normals = textures
+        .read_texture(device, queue, &textures.elevation_normal_texture, vec2(0, 0), vec2(size, size))
+        .await;
+    let biome = textures
+        .read_texture(device, queue, &textures.biome_texture, vec2(0, 0), vec2(size, size))
+        .await;
+    let water = textures
+        .read_texture(device, queue, &textures.water_texture, vec2(0, 0), vec2(size, size))
+        .await;
+
+    println!("Parse data: {} ms", parse.elapsed().as_millis());
+    timings.push(("Parse data".to_string(), parse.elapsed().as_micros() as f32 / 1000.0));
+    (
+        RawData {
+            elevation_normals,
+            biome,
+            water,
+        },
+        timings,
+    )
+}
+
+// Whether a chunk coordinate lies in the count by count window of loaded chunks starting at origin
+fn is_in_window(origin: Vector2<i32>, count: i32, (x, z): (i32, i32)) -> bool {
+    x >= origin.x && x < origin.x + count && z >= origin.y && z < origin.y + count
+}
+
+fn create_tasks<'a>(
+    compute: &'a compute::Compute,
+    map_config: &config::MapConfig,
+    elevation: bool,
+    (origin, extent): (Vector2<i32>, Vector2<u32>),
+    // The region grown into generated neighbours, used by the stages that read neighbouring texels
+    (border_origin, border_extent): (Vector2<i32>, Vector2<u32>),
+) -> Vec<Task<'a>> {
+    let stages = &map_config.stages;
+    let mut tasks = vec![];
+
+    // Erosion also runs on an apron of generated noise around the region so droplets can cross its border,
+    // unless the region covers most of the map
+    let size = map_config.tile_size * 2u32.pow(map_config.tile_depth);
+    let apron = match stages.erosion && elevation && extent.x.max(extent.y) + EROSION_APRON * 2 <= size {
+        true => EROSION_APRON,
+        false => 0,
+    };
+    let (padded_origin, padded_extent) = (origin - vec2(apron as i32, apron as i32), extent + vec2(apron * 2, apron * 2));
+
+    // Terrain
+    if elevation {
+        tasks.push(
+            Task::new("Elevation", &compute.elevation_pipeline, 1, 1)
+                .region(padded_origin, padded_extent)
+                .apron(apron),
+        );
+    }
+    if stages.erosion {
+        tasks.push(
+            Task::new("Erosion", &compute.erosion_pipeline, 1, EROSION_STAGES)
+                .depends_on(&["Elevation"])
+                .region(padded_origin, padded_extent)
+                .apron(apron),
+        );
+    }
+    if stages.thermal {
+        tasks.push(
+            Task::new(
+                "Thermal",
+                &compute.thermal_pipeline,
+                1,
+                map_config.erosion.thermal_iterations * 2,
+            )
+            .depends_on(&["Elevation", "Erosion"])
+            .region(origin, extent),
+        );
+    }
+    if stages.smooth {
+        tasks.push(
+            Task::new("Smooth", &compute.smooth_pipeline, 3, 1)
+                .depends_on(&["Elevation", "Erosion", "Thermal"])
+                .region(border_origin, border_extent),
+        );
+    }
+    if stages.water {
+        // Each iteration moves lake surfaces and river flow one texel further, so they run as many times as it takes
+        // to cross the region along a straight or diagonal line
+        let iterations = extent.x.max(extent.y);
+        tasks.push(
+            Task::new("Lakes", &compute.lakes_pipeline, 1, iterations + 1)
+                .depends_on(&["Elevation", "Erosion", "Thermal", "Smooth"])
+                .region(origin, extent),
+        );
+        tasks.push(
+            Task::new("Rivers", &compute.rivers_pipeline, 1, iterations + 2)
+                .depends_on(&["Lakes"])
+                .region(origin, extent),
+        );
+    }
+    if stages.normals {
+        tasks.push(
+            Task::new("Normals", &compute.normal_pipeline, 1, 1)
+                .depends_on(&["Elevation", "Erosion", "Thermal", "Smooth", "Rivers"])
+                .region(border_origin, border_extent),
+        );
+    }
+
+    // Biomes
+    if stages.biomes {
+        let terrain = ["Elevation", "Erosion", "Thermal", "Smooth", "Rivers", "Normals"];
+        tasks.push(
+            Task::new("Temperature", &compute.temperature_pipeline, 1, 1)
+                .depends_on(&terrain)
+                .region(origin, extent),
+        );
+        tasks.push(
+            Task::new("Moisture", &compute.moisture_pipeline, 1, 1)
+                .depends_on(&["Temperature"])
+                .region(origin, extent),
+        );
+        tasks.push(
+            Task::new("Decoration", &compute.decoration_pipeline, 1, 1)
+                .depends_on(&["Temperature", "Moisture"])
+                .region(origin, extent),
+        );
+    }
+
+    tasks
+}
@@ -0,0 +1,247 @@
+use super::{BiomeSample, Heightfield};
+use cgmath::*;
+
+const REFINE_STEPS: usize = 24;
+const MAX_WATER_DISTANCE: f32 = 64.0;
+
+// The queries that reach past the hit texel are None when the hit is too close to the edge of the generated terrain
+pub struct Hit {
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+    pub biome: Option<BiomeSample>,
+    pub slope: f32,
+    pub aspect: f32,
+    pub curvature: Option<f32>,
+    pub water_depth: Option<f32>,
+    pub water_distance: Option<f32>,
+}
+
+impl Heightfield {
+    // Walks the heightfield one texel cell at a time, only the generated part of the map can be hit
+    pub fn raycast(&self, origin: Point3<f32>, dir: Vector3<f32>) -> Option<Hit> {
+        let dir = dir.normalize();
+
+        // Straight up or down rays never cross a cell border
+        if dir.x.abs() < f32::EPSILON && dir.z.abs() < f32::EPSILON {
+            let elevation = self.get_elevation(vec2(origin.x, origin.z))?;
+            let t = (elevation - origin.y) / dir.y;
+            return match t >= 0.0 {
+                true => Some(self.create_hit(origin + dir * t, t)),
+                false => None,
+            };
+        }
+
+        let half_size = self.size as f32 / 2.0;
+        let center = self.get_center();
+        let (t_start, t_end) = clip(
+            origin,
+            dir,
+            center - vec2(half_size, half_size),
+            center + vec2(half_size, half_size),
+        )?;
+
+        let start = origin + dir * t_start;
+        let mut cell = vec2(start.x.floor(), start.z.floor());
+        let step = vec2(dir.x.signum(), dir.z.signum());
+        let t_delta = vec2(1.0 / dir.x.abs(), 1.0 / dir.z.abs());
+        let border = |c: f32, o: f32, d: f32| match d > 0.0 {
+            true => (c + 1.0 - o) / d,
+            false => (c - o) / d,
+        };
+        let mut t_next = vec2(
+            if dir.x != 0.0 { border(cell.x, origin.x, dir.x) } else { f32::MAX },
+            if dir.z != 0.0 { border(cell.y, origin.z, dir.z) } else { f32::MAX },
+        );
+
+        let mut t = t_start;
+        while t < t_end {
+            let t_exit = t_next.x.min(t_next.y).min(t_end);
+            if let Some(t_hit) = self.intersect_cell(origin, dir, cell, t, t_exit) {
+                return Some(self.create_hit(origin + dir * t_hit, t_hit));
+            }
+
+            if t_next.x < t_next.y {
+                t_next.x += t_delta.x;
+                cell.x += step.x;
+            } else {
+                t_next.y += t_delta.y;
+                cell.y += step.y;
+            }
+            t = t_exit;
+        }
+
+        None
+    }
+
+    // The surface of a cell is interpolated from its own corners, so its far border can be hit even without generated terrain behind it
+    fn intersect_cell(&self, origin: Point3<f32>, dir: Vector3<f32>, cell: Vector2<f32>, t0: f32, t1: f32) -> Option<f32> {
+        let texel = |x: f32, z: f32| self.get_position_normal(cell + vec2(x, z)).map(|(position, _)| position.y);
+        let (e00, e10, e01, e11) = (texel(0.0, 0.0)?, texel(1.0, 0.0)?, texel(0.0, 1.0)?, texel(1.0, 1.0)?);
+        let height_above = |t: f32| {
+            let p = origin + dir * t;
+            let (fx, fz) = (p.x - cell.x, p.z - cell.y);
+            let top = e00 + (e10 - e00) * fx;
+            let bottom = e01 + (e11 - e01) * fx;
+            p.y - (top + (bottom - top) * fz)
+        };
+
+        if height_above(t0) <= 0.0 {
+            return Some(t0);
+        }
+        if height_above(t1) > 0.0 {
+            return None;
+        }
+
+        let (mut above, mut below) = (t0, t1);
+        for _ in 0..REFINE_STEPS {
+            let mid = (above + below) / 2.0;
+            match height_above(mid) > 0.0 {
+                true => above = mid,
+                false => below = mid,
+            }
+        }
+
+        Some(below)
+    }
+
+    fn create_hit(&self, position: Point3<f32>, distance: f32) -> Hit {
+        let p = vec2(position.x, position.z);
+        let water_depth = match self.get_water(p) {
+            Some(height) => Some(height - position.y),
+            None if position.y < 0.0 => Some(-position.y),
+            None => None,
+        };
+
+        // The interpolated normal needs the texels on both sides, at the edge the nearest one is used
+        let normal = self
+            .get_normal(p)
+            .or_else(|| self.get_position_normal(p).map(|(_, normal)| normal))
+            .unwrap_or_else(Vector3::unit_y);
+
+        Hit {
+            position,
+            normal,
+            distance,
+            biome: self.get_biome(p),
+            slope: normal.y.max(-1.0).min(1.0).acos(),
+            aspect: normal.z.atan2(normal.x),
+            curvature: self.get_curvature(p),
+            water_depth: water_depth.filter(|depth| *depth > 0.0),
+            water_distance: self.get_distance_to_water(p, MAX_WATER_DISTANCE),
+        }
+    }
+}
+
+// Clips the ray against the horizontal extent of the map, returning the entry and exit distances
+fn clip(origin: Point3<f32>, dir: Vector3<f32>, min: Vector2<f32>, max: Vector2<f32>) -> Option<(f32, f32)> {
+    let mut t_min = 0.0f32;
+    let mut t_max = f32::MAX;
+
+    for &(o, d, lo, hi) in [(origin.x, dir.x, min.x, max.x), (origin.z, dir.z, min.y, max.y)].iter() {
+        if d == 0.0 {
+            if o < lo || o >= hi {
+                return None;
+            }
+        } else {
+            let (t0, t1) = ((lo - o) / d, (hi - o) / d);
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+    }
+
+    match t_min < t_max {
+        true => Some((t_min, t_max)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Biomes, Heightfield, RawData};
+    use crate::settings;
+    use cgmath::*;
+    use std::collections::HashSet;
+
+    const SIZE: u32 = 64;
+
+    // A single generated chunk covering -32..32 in world space, normals are taken from central differences
+    fn heightfield(elevation: impl Fn(f32, f32) -> f32) -> Heightfield {
+        let half_size = SIZE as f32 / 2.0;
+        let mut elevation_normals = vec![];
+        for z in 0..SIZE {
+            for x in 0..SIZE {
+                let (x, z) = (x as f32 - half_size, z as f32 - half_size);
+                let normal = vec3(
+                    elevation(x - 1.0, z) - elevation(x + 1.0, z),
+                    2.0,
+                    elevation(x, z - 1.0) - elevation(x, z + 1.0),
+                )
+                .normalize();
+                elevation_normals.push(vec4(elevation(x, z), normal.x, normal.y, normal.z));
+            }
+        }
+
+        let texels = (SIZE * SIZE) as usize;
+        let mut chunks = HashSet::new();
+        chunks.insert((0, 0));
+        Heightfield {
+            biomes: Biomes::load(settings::BIOMES_PATH).unwrap(),
+            size: SIZE,
+            chunk_size: SIZE,
+            origin: vec2(0, 0),
+            chunks,
+            window: None,
+            data: RawData {
+                elevation_normals,
+                biome: vec![Vector4::zero(); texels],
+                water: vec![Vector4::zero(); texels],
+            },
+        }
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 0.001, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn hits_ramp() {
+        let map = heightfield(|x, _| x * 0.5);
+        let hit = map.raycast(Point3::new(-10.0, 20.0, 0.5), vec3(1.0, -1.0, 0.0)).unwrap();
+
+        // 20 - s = 0.5 * (s - 10)
+        let s = 25.0 / 1.5;
+        assert_close(hit.position.to_vec(), vec3(-10.0 + s, 20.0 - s, 0.5));
+        assert_close(hit.normal, vec3(-0.5, 1.0, 0.0).normalize());
+        assert!((hit.distance - s * 2.0f32.sqrt()).abs() < 0.001);
+        assert_eq!(hit.water_depth, None);
+    }
+
+    #[test]
+    fn hits_pit_below_sea_level() {
+        let map = heightfield(|x, z| if x.abs() <= 4.0 && z.abs() <= 4.0 { -5.0 } else { 2.0 });
+        let hit = map.raycast(Point3::new(0.0, 10.0, 0.5), vec3(0.1, -1.0, 0.0)).unwrap();
+
+        assert_close(hit.position.to_vec(), vec3(1.5, -5.0, 0.5));
+        assert_close(hit.normal, Vector3::unit_y());
+        assert!((hit.water_depth.unwrap() - 5.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn misses_outside_generated_window() {
+        let map = heightfield(|_, _| 1.0);
+        assert!(map.raycast(Point3::new(20.0, 10.0, 0.5), vec3(1.0, 0.1, 0.0)).is_none());
+        assert!(map.raycast(Point3::new(30.0, 10.0, 0.5), vec3(1.0, -0.1, 0.0)).is_none());
+    }
+
+    #[test]
+    fn hits_last_cell_of_generated_window() {
+        let map = heightfield(|_, _| 1.0);
+        let hit = map.raycast(Point3::new(21.5, 10.0, 0.5), vec3(1.0, -1.0, 0.0)).unwrap();
+
+        assert_close(hit.position.to_vec(), vec3(30.5, 1.0, 0.5));
+        assert_close(hit.normal, Vector3::unit_y());
+        assert_eq!(hit.water_depth, None);
+        assert_eq!(hit.curvature, None);
+    }
+}
@@ -0,0 +1,127 @@
+use super::{BiomeSample, Heightfield};
+use cgmath::*;
+
+// Terrain queries in world coordinates, all of them return None outside the generated part of the map
+impl Heightfield {
+    // Nearest texel, the position is snapped to the texel grid
+    pub fn get_position_normal(&self, p: Vector2<f32>) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.get_index(p)
+            .map(|i| self.data.elevation_normals[i])
+            .map(|v| (vec3(p.x.floor(), v.x, p.y.floor()), vec3(v.y, v.z, v.w)))
+    }
+
+    pub fn get_elevation(&self, p: Vector2<f32>) -> Option<f32> {
+        let (x, z) = (p.x.floor(), p.y.floor());
+        let (e00, e10) = (self.get_texel(x, z)?.x, self.get_texel(x + 1.0, z)?.x);
+        let (e01, e11) = (self.get_texel(x, z + 1.0)?.x, self.get_texel(x + 1.0, z + 1.0)?.x);
+
+        let top = e00 + (e10 - e00) * (p.x - x);
+        let bottom = e01 + (e11 - e01) * (p.x - x);
+        Some(top + (bottom - top) * (p.y - z))
+    }
+
+    // Catmull-Rom interpolation over the surrounding 4x4 texels, smoother than bilinear but may overshoot at sharp ridges
+    pub fn get_elevation_bicubic(&self, p: Vector2<f32>) -> Option<f32> {
+        let (x, z) = (p.x.floor(), p.y.floor());
+        let mut rows = [0.0; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            let z = z + i as f32 - 1.0;
+            *row = cubic(
+                self.get_texel(x - 1.0, z)?.x,
+                self.get_texel(x, z)?.x,
+                self.get_texel(x + 1.0, z)?.x,
+                self.get_texel(x + 2.0, z)?.x,
+                p.x - x,
+            );
+        }
+
+        Some(cubic(rows[0], rows[1], rows[2], rows[3], p.y - z))
+    }
+
+    pub fn get_normal(&self, p: Vector2<f32>) -> Option<Vector3<f32>> {
+        let (x, z) = (p.x.floor(), p.y.floor());
+        let normal = |x: f32, z: f32| self.get_texel(x, z).map(|v| vec3(v.y, v.z, v.w));
+        let (n00, n10) = (normal(x, z)?, normal(x + 1.0, z)?);
+        let (n01, n11) = (normal(x, z + 1.0)?, normal(x + 1.0, z + 1.0)?);
+
+        let top = n00.lerp(n10, p.x - x);
+        let bottom = n01.lerp(n11, p.x - x);
+        Some(top.lerp(bottom, p.y - z).normalize())
+    }
+
+    // Angle between the surface and the horizontal plane in radians
+    pub fn get_slope(&self, p: Vector2<f32>) -> Option<f32> {
+        self.get_normal(p).map(|n| n.y.max(-1.0).min(1.0).acos())
+    }
+
+    // Downhill direction in radians, measured from the x axis towards the z axis
+    pub fn get_aspect(&self, p: Vector2<f32>) -> Option<f32> {
+        self.get_normal(p).map(|n| n.z.atan2(n.x))
+    }
+
+    // Laplacian of the elevation, positive in valleys and negative on ridges
+    pub fn get_curvature(&self, p: Vector2<f32>) -> Option<f32> {
+        let center = self.get_elevation(p)?;
+        let neighbours = [vec2(-1.0, 0.0), vec2(1.0, 0.0), vec2(0.0, -1.0), vec2(0.0, 1.0)]
+            .iter()
+            .map(|o| self.get_elevation(p + o))
+            .sum::<Option<f32>>()?;
+
+        Some(neighbours - center * 4.0)
+    }
+
+    // Distance to the closest sea, lake or river texel, capped at max_distance
+    pub fn get_distance_to_water(&self, p: Vector2<f32>, max_distance: f32) -> Option<f32> {
+        if self.is_water(p)? {
+            return Some(0.0);
+        }
+
+        let (x, z) = (p.x.floor(), p.y.floor());
+        let mut closest = max_distance;
+        let mut radius = 1.0;
+        while radius <= closest.ceil() {
+            for i in -radius as i32..=radius as i32 {
+                let i = i as f32;
+                let ring = [
+                    vec2(x + i, z - radius),
+                    vec2(x + i, z + radius),
+                    vec2(x - radius, z + i),
+                    vec2(x + radius, z + i),
+                ];
+                for q in ring.iter() {
+                    if self.is_water(*q) == Some(true) {
+                        closest = closest.min((q - vec2(x, z)).magnitude());
+                    }
+                }
+            }
+            radius += 1.0;
+        }
+
+        Some(closest)
+    }
+
+    pub fn get_biome(&self, p: Vector2<f32>) -> Option<BiomeSample> {
+        self.get_index(p).map(|i| self.data.biome[i]).map(|b| self.biomes.resolve(b.x, b.y))
+    }
+
+    // Surface height of lakes and rivers, None on dry land and at sea
+    pub fn get_water(&self, p: Vector2<f32>) -> Option<f32> {
+        match self.get_index(p).map(|i| self.data.water[i]) {
+            Some(w) if w.y > 0.0 => Some(w.x),
+            _ => None,
+        }
+    }
+
+    fn is_water(&self, p: Vector2<f32>) -> Option<bool> {
+        let index = self.get_index(p)?;
+        Some(self.data.water[index].y > 0.0 || self.data.elevation_normals[index].x < 0.0)
+    }
+
+    fn get_texel(&self, x: f32, z: f32) -> Option<Vector4<f32>> {
+        self.get_index(vec2(x, z)).map(|i| self.data.elevation_normals[i])
+    }
+}
+
+fn cubic(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    p1 + 0.5 * t * (p2 - p0 + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)))
+}
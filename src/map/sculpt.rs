@@ -0,0 +1,216 @@
+use super::{journal::Texel, textures, Heightfield, Map};
+use cgmath::*;
+
+const RAISE_SPEED: f32 = 20.0;
+const FLATTEN_SPEED: f32 = 4.0;
+const SMOOTH_SPEED: f32 = 8.0;
+const NOISE_SPEED: f32 = 10.0;
+const NOISE_SCALE: f32 = 0.15;
+// Degrees and moisture per second, moisture is kept within the 0 to 1 range of generation
+const TEMPERATURE_SPEED: f32 = 10.0;
+const MOISTURE_SPEED: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Brush {
+    Raise,
+    Lower,
+    Flatten,
+    Smooth,
+    Noise,
+    Warm,
+    Cool,
+    Wet,
+    Dry,
+}
+
+impl Map {
+    // Applies a brush for the given number of seconds, returns the world space area that changed
+    pub fn sculpt(
+        &mut self,
+        queue: &wgpu::Queue,
+        brush: Brush,
+        center: Vector2<f32>,
+        radius: f32,
+        seconds: f32,
+    ) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let mut heightfield = self.heightfield.write().unwrap();
+        let target = heightfield.get_elevation(center)?;
+        let min = vec2((center.x - radius).floor(), (center.y - radius).floor());
+        let max = vec2((center.x + radius).ceil(), (center.y + radius).ceil());
+
+        // Changes are collected first so smoothing only reads unmodified heights
+        let mut changes = vec![];
+        for z in min.y as i32..=max.y as i32 {
+            for x in min.x as i32..=max.x as i32 {
+                let p = vec2(x as f32, z as f32);
+                let distance = p.distance(center);
+                let index = match heightfield.get_index(p) {
+                    Some(index) if distance <= radius => index,
+                    _ => continue,
+                };
+
+                let t = 1.0 - distance / radius;
+                let falloff = t * t * (3.0 - 2.0 * t);
+                let mut texel = heightfield.get_editable(index);
+                let elevation = texel.elevation;
+                match brush {
+                    Brush::Raise => texel.elevation += RAISE_SPEED * falloff * seconds,
+                    Brush::Lower => texel.elevation -= RAISE_SPEED * falloff * seconds,
+                    Brush::Flatten => texel.elevation += (target - elevation) * (FLATTEN_SPEED * falloff * seconds).min(1.0),
+                    Brush::Smooth => {
+                        let average = heightfield.get_neighbourhood(p).iter().sum::<f32>() / 9.0;
+                        texel.elevation += (average - elevation) * (SMOOTH_SPEED * falloff * seconds).min(1.0);
+                    }
+                    Brush::Noise => texel.elevation += (value_noise(p * NOISE_SCALE) * 2.0 - 1.0) * NOISE_SPEED * falloff * seconds,
+                    Brush::Warm => texel.temperature += TEMPERATURE_SPEED * falloff * seconds,
+                    Brush::Cool => texel.temperature -= TEMPERATURE_SPEED * falloff * seconds,
+                    Brush::Wet => texel.moisture = (texel.moisture + MOISTURE_SPEED * falloff * seconds).min(1.0),
+                    Brush::Dry => texel.moisture = (texel.moisture - MOISTURE_SPEED * falloff * seconds).max(0.0),
+                }
+                changes.push(((x, z), index, texel));
+            }
+        }
+
+        for (p, index, texel) in changes {
+            self.journal.record(p, heightfield.get_editable(index), texel);
+            heightfield.set_editable(index, texel);
+        }
+
+        Some(heightfield.update_region(queue, &self.textures, min, max))
+    }
+
+    // Closes the current stroke so it can be undone as one step
+    pub fn end_stroke(&mut self) {
+        if self.journal.end_stroke() {
+            self.save_journal();
+        }
+    }
+
+    pub fn undo(&mut self, queue: &wgpu::Queue) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let changes = self.journal.undo()?;
+        self.save_journal();
+        self.restore(queue, changes)
+    }
+
+    pub fn redo(&mut self, queue: &wgpu::Queue) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let changes = self.journal.redo()?;
+        self.save_journal();
+        self.restore(queue, changes)
+    }
+
+    // Replays the saved edits inside a rectangle of world texels on top of freshly generated terrain
+    pub fn apply_edits(&mut self, queue: &wgpu::Queue, min: Vector2<i32>, max: Vector2<i32>) {
+        let mut heightfield = self.heightfield.write().unwrap();
+        let mut bounds: Option<(Vector2<f32>, Vector2<f32>)> = None;
+        for ((x, z), diff) in self.journal.get_diffs(min, max) {
+            let p = vec2(x as f32, z as f32);
+            if let Some(index) = heightfield.get_index(p) {
+                let texel = heightfield.get_editable(index) + diff;
+                heightfield.set_editable(index, texel);
+                bounds = Some(extend(bounds, p));
+            }
+        }
+
+        if let Some((min, max)) = bounds {
+            heightfield.update_region(queue, &self.textures, min, max);
+        }
+    }
+
+    fn restore(&mut self, queue: &wgpu::Queue, changes: Vec<((i32, i32), Texel)>) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        // Texels outside the window are only restored in the journal and pick up the change when they are generated again
+        let mut heightfield = self.heightfield.write().unwrap();
+        let mut bounds = None;
+        for ((x, z), texel) in changes {
+            let p = vec2(x as f32, z as f32);
+            if let Some(index) = heightfield.get_index(p) {
+                heightfield.set_editable(index, texel);
+                bounds = Some(extend(bounds, p));
+            }
+        }
+
+        let (min, max) = bounds?;
+        Some(heightfield.update_region(queue, &self.textures, min, max))
+    }
+
+    fn save_journal(&self) {
+        if let Err(error) = self.journal.save() {
+            println!("Failed to save edits: {}", error);
+        }
+    }
+}
+
+impl Heightfield {
+    fn get_editable(&self, index: usize) -> Texel {
+        let biome = self.data.biome[index];
+        Texel {
+            elevation: self.data.elevation_normals[index].x,
+            temperature: biome.x,
+            moisture: biome.y,
+        }
+    }
+
+    fn set_editable(&mut self, index: usize, texel: Texel) {
+        self.data.elevation_normals[index].x = texel.elevation;
+        self.data.biome[index].x = texel.temperature;
+        self.data.biome[index].y = texel.moisture;
+    }
+
+    // Normals depend on the neighbouring heights so they change one texel beyond the edited area
+    fn update_region(
+        &mut self,
+        queue: &wgpu::Queue,
+        textures: &textures::Textures,
+        min: Vector2<f32>,
+        max: Vector2<f32>,
+    ) -> (Vector2<f32>, Vector2<f32>) {
+        let (min, max) = (min - vec2(1.0, 1.0), max + vec2(1.0, 1.0));
+        for z in min.y as i32..=max.y as i32 {
+            for x in min.x as i32..=max.x as i32 {
+                let p = vec2(x as f32, z as f32);
+                if let Some(index) = self.get_index(p) {
+                    let h = self.get_neighbourhood(p);
+                    let normal = vec3(h[3] - h[5], 1.0, h[1] - h[7]).normalize();
+                    let elevation = self.data.elevation_normals[index].x;
+                    self.data.elevation_normals[index] = vec4(elevation, normal.x, normal.y, normal.z);
+                }
+            }
+        }
+
+        self.upload_region(queue, textures, min, max);
+        (min, max)
+    }
+
+    // Elevations of the 3x3 texels around p in row order, missing neighbours repeat the center
+    fn get_neighbourhood(&self, p: Vector2<f32>) -> [f32; 9] {
+        let center = self.get_index(p).map_or(0.0, |i| self.data.elevation_normals[i].x);
+        let mut heights = [center; 9];
+        for (i, height) in heights.iter_mut().enumerate() {
+            let offset = vec2((i % 3) as f32 - 1.0, (i / 3) as f32 - 1.0);
+            if let Some(index) = self.get_index(p + offset) {
+                *height = self.data.elevation_normals[index].x;
+            }
+        }
+        heights
+    }
+}
+
+fn extend(bounds: Option<(Vector2<f32>, Vector2<f32>)>, p: Vector2<f32>) -> (Vector2<f32>, Vector2<f32>) {
+    match bounds {
+        Some((min, max)) => (vec2(min.x.min(p.x), min.y.min(p.y)), vec2(max.x.max(p.x), max.y.max(p.y))),
+        None => (p, p),
+    }
+}
+
+fn hash(p: Vector2<f32>) -> f32 {
+    ((p.x * 12.9898 + p.y * 78.233).sin() * 43758.5453).fract().abs()
+}
+
+fn value_noise(p: Vector2<f32>) -> f32 {
+    let i = vec2(p.x.floor(), p.y.floor());
+    let f = p - i;
+    let u = vec2(f.x * f.x * (3.0 - 2.0 * f.x), f.y * f.y * (3.0 - 2.0 * f.y));
+
+    let top = hash(i) + (hash(i + vec2(1.0, 0.0)) - hash(i)) * u.x;
+    let bottom = hash(i + vec2(0.0, 1.0)) + (hash(i + vec2(1.0, 1.0)) - hash(i + vec2(0.0, 1.0))) * u.x;
+    top + (bottom - top) * u.y
+}
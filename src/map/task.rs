@@ -0,0 +1,67 @@
+use cgmath::*;
+
+pub struct Task<'a> {
+    pub label: String,
+    pub pipeline: &'a wgpu::ComputePipeline,
+    pub run_times: u32,
+    pub stage_count: u32,
+    pub dependencies: Vec<String>,
+    pub region: Option<(Vector2<i32>, Vector2<u32>)>,
+    pub apron: u32,
+}
+
+impl<'a> Task<'a> {
+    pub fn new(label: &str, pipeline: &'a wgpu::ComputePipeline, run_times: u32, stage_count: u32) -> Self {
+        Self {
+            label: label.to_string(),
+            pipeline,
+            run_times,
+            stage_count,
+            dependencies: vec![],
+            region: None,
+            apron: 0,
+        }
+    }
+
+    // Limits the task to a rectangle of the map, given as origin and extent in unwrapped map coordinates
+    pub fn region(mut self, origin: Vector2<i32>, extent: Vector2<u32>) -> Self {
+        self.region = Some((origin, extent));
+        self
+    }
+
+    // Border of the region that is only read, see in_interior in compute.glsl
+    pub fn apron(mut self, apron: u32) -> Self {
+        self.apron = apron;
+        self
+    }
+
+    pub fn depends_on(mut self, labels: &[&str]) -> Self {
+        self.dependencies.extend(labels.iter().map(|l| l.to_string()));
+        self
+    }
+}
+
+// Orders tasks so that every task runs after its dependencies, otherwise keeping the order they were added in.
+// Dependencies that are not part of the graph are treated as already satisfied, e.g. elevation from an imported heightmap.
+pub fn sort<'a>(tasks: Vec<&'a Task<'a>>) -> Vec<&'a Task<'a>> {
+    let mut remaining = tasks;
+    let mut sorted: Vec<&'a Task<'a>> = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|task| {
+            task.dependencies
+                .iter()
+                .all(|d| sorted.iter().any(|t| &t.label == d) || !remaining.iter().any(|t| &t.label == d))
+        });
+
+        match ready {
+            Some(i) => sorted.push(remaining.remove(i)),
+            None => panic!(
+                "Cyclic dependency between compute tasks: {}",
+                remaining.iter().map(|t| t.label.as_str()).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    sorted
+}
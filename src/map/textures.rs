@@ -0,0 +1,242 @@
+use cgmath::*;
+use futures::FutureExt;
+use std::{future::Future, pin::Pin, sync::Mutex};
+
+use crate::texture;
+
+type Mapping = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+// A copy of a texture region on its way to the CPU, the device has to be polled for it to arrive.
+// The mapping future is not Sync, the mutex keeps the map shareable with the node builders.
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    mapping: Mutex<Option<Mapping>>,
+}
+
+impl Readback {
+    pub fn is_ready(&mut self) -> bool {
+        let mapping = self.mapping.get_mut().unwrap();
+        match mapping.as_mut().map(|mapping| mapping.now_or_never()) {
+            Some(Some(result)) => {
+                result.unwrap();
+                *mapping = None;
+                true
+            }
+            Some(None) => false,
+            None => true,
+        }
+    }
+
+    pub async fn wait(&mut self) {
+        let mapping = self.mapping.get_mut().unwrap().take();
+        if let Some(mapping) = mapping {
+            mapping.await.unwrap();
+        }
+    }
+
+    pub fn data(&self) -> Vec<Vector4<f32>> {
+        let data = self.buffer.slice(..).get_mapped_range();
+        unsafe { data.align_to::<Vector4<f32>>().1 }.to_vec()
+    }
+}
+
+pub struct Textures {
+    pub elevation_normal: wgpu::TextureView,
+    pub elevation_normal_texture: wgpu::Texture,
+    pub biome: wgpu::TextureView,
+    pub biome_texture: wgpu::Texture,
+    pub water: wgpu::TextureView,
+    pub water_texture: wgpu::Texture,
+    pub scratch: wgpu::TextureView,
+    pub scratch_texture: wgpu::Texture,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Textures {
+    pub fn new(device: &wgpu::Device, size: u32) -> Self {
+        let texture_descriptor = &wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsage::STORAGE | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC | wgpu::TextureUsage::COPY_DST,
+        };
+
+        let elevation_normal_texture = device.create_texture(texture_descriptor);
+        let biome_texture = device.create_texture(texture_descriptor);
+        let water_texture = device.create_texture(texture_descriptor);
+
+        let elevation_normal = elevation_normal_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let biome = biome_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let water = water_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Working copy for the compute stages that can't update the map textures in place
+        let scratch_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("scratch_texture"),
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsage::STORAGE,
+            ..*texture_descriptor
+        });
+        let scratch = scratch_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = texture::create_sampler(device, wgpu::AddressMode::Repeat, wgpu::FilterMode::Linear);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("compute_texture_bind_group_layout"),
+            entries: &[
+                texture::create_bind_group_layout(0, wgpu::TextureSampleType::Float { filterable: true }),
+                texture::create_bind_group_layout(1, wgpu::TextureSampleType::Float { filterable: true }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: false,
+                    },
+                    count: None,
+                },
+                texture::create_bind_group_layout(3, wgpu::TextureSampleType::Float { filterable: true }),
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("compute_texture"),
+            layout: &bind_group_layout,
+            entries: &[
+                texture::create_bind_group_entry(0, &elevation_normal),
+                texture::create_bind_group_entry(1, &biome),
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                texture::create_bind_group_entry(3, &water),
+            ],
+        });
+
+        Self {
+            elevation_normal,
+            elevation_normal_texture,
+            biome,
+            biome_texture,
+            water,
+            water_texture,
+            scratch,
+            scratch_texture,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn write_texture(&self, queue: &wgpu::Queue, texture: &wgpu::Texture, data: &[Vector4<f32>], size: u32) {
+        self.write_region(queue, texture, data, vec2(0, 0), vec2(size, size));
+    }
+
+    pub fn write_region(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        data: &[Vector4<f32>],
+        origin: Vector2<u32>,
+        extent: Vector2<u32>,
+    ) {
+        let data: Vec<[f32; 4]> = data.iter().map(|v| (*v).into()).collect();
+
+        queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.x,
+                    y: origin.y,
+                    z: 0,
+                },
+            },
+            bytemuck::cast_slice(&data),
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: std::mem::size_of::<f32>() as u32 * extent.x * 4,
+                rows_per_image: extent.y,
+            },
+            wgpu::Extent3d {
+                width: extent.x,
+                height: extent.y,
+                depth: 1,
+            },
+        );
+    }
+
+    pub async fn read_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        origin: Vector2<u32>,
+        extent: Vector2<u32>,
+    ) -> Vec<Vector4<f32>> {
+        let mut readback = self.start_read(device, queue, texture, origin, extent);
+        device.poll(wgpu::Maintain::Wait);
+        readback.wait().await;
+        readback.data()
+    }
+
+    // The width of the region has to be a multiple of 16 texels to satisfy the row alignment of buffer copies
+    pub fn start_read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        origin: Vector2<u32>,
+        extent: Vector2<u32>,
+    ) -> Readback {
+        let f32_size = std::mem::size_of::<f32>() as f32;
+
+        let output_buffer_size = (f32_size * extent.x as f32 * extent.y as f32 * 4.0) as wgpu::BufferAddress;
+        let output_buffer_desc = wgpu::BufferDescriptor {
+            size: output_buffer_size,
+            usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+            label: None,
+            mapped_at_creation: false,
+        };
+        let output_buffer = device.create_buffer(&output_buffer_desc);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.x,
+                    y: origin.y,
+                    z: 0,
+                },
+            },
+            wgpu::BufferCopyView {
+                buffer: &output_buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: f32_size as u32 * extent.x * 4,
+                    rows_per_image: extent.y,
+                },
+            },
+            wgpu::Extent3d {
+                width: extent.x,
+                height: extent.y,
+                depth: 1,
+            },
+        );
+
+        queue.submit(Some(encoder.finish()));
+
+        let mapping = output_buffer.slice(..).map_async(wgpu::MapMode::Read);
+        Readback {
+            buffer: output_buffer,
+            mapping: Mutex::new(Some(Box::pin(mapping))),
+        }
+    }
+}
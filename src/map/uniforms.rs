@@ -0,0 +1,112 @@
+use crate::{config, settings};
+use std::num::NonZeroU64;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uniforms {
+    pub origin: [i32; 2],
+    pub extent: [u32; 2],
+    pub sea_level: f32,
+    pub horizontal_scale: f32,
+    pub vertical_scale: f32,
+    pub size: u32,
+    pub octaves: u32,
+    pub current_stage: u32,
+    pub stage_count: u32,
+    pub droplet_steps: u32,
+    pub deposition_rate: f32,
+    pub erosion_rate: f32,
+    pub erosion_speed: f32,
+    pub erosion_friction: f32,
+    pub talus: f32,
+    pub thermal_rate: f32,
+    pub apron: u32,
+}
+
+pub const UNIFORMS_SIZE: u64 = std::mem::size_of::<Uniforms>() as u64;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NoiseLayerUniforms {
+    pub kind: u32,
+    pub octaves: u32,
+    pub mask: i32,
+    pub frequency: f32,
+    pub amplitude: f32,
+    pub warp: f32,
+    pub padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NoiseStackUniforms {
+    pub layers: [NoiseLayerUniforms; settings::MAX_NOISE_LAYERS],
+    pub layer_count: u32,
+    pub padding: [u32; 3],
+}
+
+impl NoiseStackUniforms {
+    pub fn new(layers: &[config::NoiseLayerConfig]) -> Self {
+        let mut uniforms: Self = bytemuck::Zeroable::zeroed();
+        for (i, layer) in layers.iter().enumerate() {
+            uniforms.layers[i] = NoiseLayerUniforms {
+                kind: layer.kind as u32,
+                octaves: layer.octaves,
+                mask: layer.mask.map_or(-1, |mask| mask as i32),
+                frequency: layer.frequency,
+                amplitude: layer.amplitude,
+                warp: layer.warp,
+                padding: [0.0; 2],
+            };
+        }
+        uniforms.layer_count = layers.len() as u32;
+        uniforms
+    }
+}
+
+// All stages share one buffer, each entry is padded to the dynamic offset alignment
+pub struct UniformBuffer {
+    pub data: Vec<Uniforms>,
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl UniformBuffer {
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, data: Vec<Uniforms>, noise_stack: &wgpu::Buffer) -> Self {
+        let mut contents = vec![0u8; data.len() * wgpu::BIND_BUFFER_ALIGNMENT as usize];
+        for (i, uniforms) in data.iter().enumerate() {
+            let offset = i * wgpu::BIND_BUFFER_ALIGNMENT as usize;
+            contents[offset..offset + UNIFORMS_SIZE as usize].copy_from_slice(bytemuck::bytes_of(uniforms));
+        }
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("uniform_buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("uniform_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(UNIFORMS_SIZE),
+                    },
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: noise_stack.as_entire_binding(),
+                },
+            ],
+        });
+        Self { data, buffer, bind_group }
+    }
+
+    pub fn offset(&self, index: usize) -> u32 {
+        (index as u64 * wgpu::BIND_BUFFER_ALIGNMENT) as u32
+    }
+}
@@ -56,4 +56,13 @@ impl Mesh {
     pub fn get_extra_bool(&self, name: &str) -> bool {
         *self.extras.get(name).unwrap_or(&0.0) == 1.0
     }
+
+    // Names of all enabled flags starting with prefix, e.g. biome_grassland = 1 gives grassland
+    pub fn get_extra_flags(&self, prefix: &str) -> Vec<String> {
+        self.extras
+            .iter()
+            .filter(|(key, value)| key.starts_with(prefix) && **value == 1.0)
+            .map(|(key, _)| key[prefix.len()..].to_string())
+            .collect()
+    }
 }
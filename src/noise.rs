@@ -1,11 +1,17 @@
-use crate::{settings, texture};
+use crate::{config, map, texture};
+use cgmath::*;
 use rand::prelude::*;
 use rand_pcg::Pcg64;
 use rand_seeder::Seeder;
 use std::time::Instant;
 
+// Largest allowed difference between the CPU and GPU noise, relative to map.vertical_scale
+pub const ELEVATION_TOLERANCE: f32 = 0.001;
+
 pub struct Noise {
     texture_view: wgpu::TextureView,
+    data: Vec<f32>,
+    size: u32,
 }
 
 pub struct NoiseBindings {
@@ -14,7 +20,7 @@ pub struct NoiseBindings {
 }
 
 impl Noise {
-    pub async fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+    pub async fn new(device: &wgpu::Device, queue: &wgpu::Queue, seed: &str) -> Self {
         let now = Instant::now();
         let size = 512;
         let texture_size = wgpu::Extent3d {
@@ -35,7 +41,7 @@ impl Noise {
         let texture = device.create_texture(frame_descriptor);
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let mut rng: Pcg64 = Seeder::from(settings::MAP_SEED).make_rng();
+        let mut rng: Pcg64 = Seeder::from(seed).make_rng();
         let mut noise_data = vec![];
         for _ in 0..size {
             for _ in 0..size {
@@ -60,7 +66,11 @@ impl Noise {
         );
 
         println!("Noise: {} ms", now.elapsed().as_millis());
-        Self { texture_view }
+        Self {
+            texture_view,
+            data: noise_data,
+            size,
+        }
     }
 
     pub fn create_bindings(&self, device: &wgpu::Device) -> NoiseBindings {
@@ -106,4 +116,249 @@ impl Noise {
             bind_group,
         }
     }
+
+    // CPU versions of the functions in noise.glsl, they follow the shader operation by operation so both give the same results
+
+    pub fn random(&self, st: Vector2<f32>) -> f32 {
+        let size = self.size as f32;
+        let (x, z) = (st.x.rem_euclid(size) as u32, st.y.rem_euclid(size) as u32);
+        self.data[(z * self.size + x) as usize]
+    }
+
+    pub fn value(&self, st: Vector2<f32>) -> f32 {
+        let i = vec2(st.x.floor(), st.y.floor());
+        let f = st - i;
+        let a = self.random(i);
+        let b = self.random(i + vec2(1.0, 0.0));
+        let c = self.random(i + vec2(0.0, 1.0));
+        let d = self.random(i + vec2(1.0, 1.0));
+
+        let u = vec2(f.x * f.x * (3.0 - 2.0 * f.x), f.y * f.y * (3.0 - 2.0 * f.y));
+        a + (b - a) * u.x + (c - a) * u.y * (1.0 - u.x) + (d - b) * u.x * u.y
+    }
+
+    fn gradient(&self, i: Vector2<f32>) -> Vector2<f32> {
+        let angle = self.random(i) * 6.2831853;
+        vec2(angle.cos(), angle.sin())
+    }
+
+    pub fn perlin(&self, st: Vector2<f32>) -> f32 {
+        let i = vec2(st.x.floor(), st.y.floor());
+        let f = st - i;
+        let a = self.gradient(i).dot(f);
+        let b = self.gradient(i + vec2(1.0, 0.0)).dot(f - vec2(1.0, 0.0));
+        let c = self.gradient(i + vec2(0.0, 1.0)).dot(f - vec2(0.0, 1.0));
+        let d = self.gradient(i + vec2(1.0, 1.0)).dot(f - vec2(1.0, 1.0));
+
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let u = vec2(fade(f.x), fade(f.y));
+        let (ab, cd) = (a + (b - a) * u.x, c + (d - c) * u.x);
+        (ab + (cd - ab) * u.y) * 0.7071 + 0.5
+    }
+
+    pub fn simplex(&self, st: Vector2<f32>) -> f32 {
+        const F2: f32 = 0.3660254;
+        const G2: f32 = 0.2113249;
+
+        let s = (st.x + st.y) * F2;
+        let i = vec2((st.x + s).floor(), (st.y + s).floor());
+        let t = (i.x + i.y) * G2;
+        let x0 = st - i + vec2(t, t);
+        let o = if x0.x > x0.y { vec2(1.0, 0.0) } else { vec2(0.0, 1.0) };
+        let x1 = x0 - o + vec2(G2, G2);
+        let x2 = x0 - vec2(1.0, 1.0) + vec2(2.0 * G2, 2.0 * G2);
+
+        let corner = |x: Vector2<f32>, g: Vector2<f32>| {
+            let t = (0.5 - x.dot(x)).max(0.0);
+            let t = t * t;
+            t * t * g.dot(x)
+        };
+        let n = corner(x0, self.gradient(i)) + corner(x1, self.gradient(i + o)) + corner(x2, self.gradient(i + vec2(1.0, 1.0)));
+        n * 35.0 + 0.5
+    }
+
+    pub fn worley(&self, st: Vector2<f32>) -> f32 {
+        let i = vec2(st.x.floor(), st.y.floor());
+        let f = st - i;
+        let mut d: f32 = 1.0;
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let cell = vec2(x as f32, y as f32);
+                let point = cell + vec2(self.random(i + cell), self.random(i + cell + vec2(57.0, 113.0)));
+                d = d.min((point - f).magnitude());
+            }
+        }
+        d
+    }
+
+    pub fn layer_noise(&self, kind: config::NoiseType, st: Vector2<f32>) -> f32 {
+        match kind {
+            config::NoiseType::Perlin => self.perlin(st),
+            config::NoiseType::Simplex => self.simplex(st),
+            config::NoiseType::Worley => self.worley(st),
+            _ => self.value(st),
+        }
+    }
+
+    pub fn layer_fbm(&self, kind: config::NoiseType, st: Vector2<f32>, octaves: u32) -> f32 {
+        let mut st = st;
+        let mut v = 0.0;
+        let mut a = 0.5;
+        let mut weight = 1.0;
+        let shift = vec2(100.0, 100.0);
+
+        let rot = Matrix2::new(0.5f32.cos(), 0.5f32.sin(), -(0.5f32.sin()), 0.5f32.cos());
+        for _ in 0..octaves {
+            let n = match kind {
+                config::NoiseType::Ridged => {
+                    let n = 1.0 - (self.perlin(st) * 2.0 - 1.0).abs();
+                    let n = n * n * weight;
+                    weight = (n * 2.0).max(0.0).min(1.0);
+                    n
+                }
+                _ => self.layer_noise(kind, st),
+            };
+            v += a * n;
+            st = rot * st * 2.0 + shift;
+            a *= 0.5;
+        }
+        v
+    }
+
+    pub fn fbm(&self, st: Vector2<f32>, octaves: u32) -> f32 {
+        self.layer_fbm(config::NoiseType::Value, st, octaves)
+    }
+
+    // Elevation before any erosion or smoothing, mirrors terrain-elev.comp for a position in map coordinates
+    pub fn elevation(&self, p: Vector2<f32>, map_config: &config::MapConfig) -> f32 {
+        let xz = p * map_config.horizontal_scale;
+        if map_config.noise.is_empty() {
+            let octaves = map_config.octaves;
+            let q = vec2(self.fbm(xz, octaves), self.fbm(xz + vec2(1.0, 1.0), octaves));
+            let r = vec2(
+                self.fbm(xz + q + vec2(1.7 + 0.15, 9.2 + 0.15), octaves),
+                self.fbm(xz + q + vec2(8.3 + 0.126, 2.8 + 0.126), octaves),
+            );
+            return (self.fbm(xz + r, octaves) - map_config.sea_level) * map_config.vertical_scale;
+        }
+
+        let mut values = vec![];
+        let mut elevation = 0.0;
+        for layer in map_config.noise.iter() {
+            let mut st = xz * layer.frequency;
+            if layer.warp > 0.0 {
+                let q = vec2(
+                    self.layer_fbm(layer.kind, st + vec2(1.7, 9.2), layer.octaves),
+                    self.layer_fbm(layer.kind, st + vec2(8.3, 2.8), layer.octaves),
+                );
+                st += q * layer.warp;
+            }
+
+            let value = self.layer_fbm(layer.kind, st, layer.octaves);
+            let mask = layer.mask.map_or(1.0, |mask| values[mask]);
+            elevation += value * layer.amplitude * mask;
+            values.push(value);
+        }
+
+        (elevation - map_config.sea_level) * map_config.vertical_scale
+    }
+
+    // Largest difference between the generated elevation and the CPU noise over a grid of samples
+    pub fn max_difference(&self, map: &map::Map, map_config: &config::MapConfig) -> f32 {
+        let half_size = map.size as f32 / 2.0;
+        let heightfield = map.read();
+        let mut difference: f32 = 0.0;
+        for z in (0..map.size).step_by(7) {
+            for x in (0..map.size).step_by(7) {
+                let p = vec2(x as f32, z as f32);
+                if let Some(elevation) = heightfield.get_elevation(p - vec2(half_size, half_size)) {
+                    difference = difference.max((elevation - self.elevation(p, map_config)).abs());
+                }
+            }
+        }
+        difference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn create_device() -> (wgpu::Device, wgpu::Queue, f32) {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+        }))
+        .expect("No GPU adapter available to compare the noise with");
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))
+        .expect("Failed to create a device to compare the noise with");
+        (device, queue, adapter.get_timestamp_period())
+    }
+
+    fn layer(kind: config::NoiseType, mask: Option<usize>) -> config::NoiseLayerConfig {
+        config::NoiseLayerConfig {
+            kind,
+            frequency: 2.0,
+            amplitude: 0.5,
+            octaves: 3,
+            warp: 0.5,
+            mask,
+        }
+    }
+
+    // Runs terrain-elev without the later stages and compares it with the CPU noise
+    fn assert_matches_gpu(noise_layers: Vec<config::NoiseLayerConfig>) {
+        let (device, queue, timestamp_period) = create_device();
+
+        let mut map_config = config::WorldConfig::default().map;
+        map_config.seed = "NOISE_TEST".to_string();
+        map_config.tile_size = 64;
+        map_config.tile_depth = 0;
+        map_config.cache = false;
+        map_config.chunks = 1;
+        map_config.noise = noise_layers;
+        let stages = &mut map_config.stages;
+        stages.erosion = false;
+        stages.thermal = false;
+        stages.smooth = false;
+        stages.water = false;
+        stages.biomes = false;
+
+        let noise = block_on(Noise::new(&device, &queue, &map_config.seed));
+        let map = block_on(map::Map::new(&device, &queue, &noise, &map_config, timestamp_period));
+        let difference = noise.max_difference(&map, &map_config);
+        assert!(
+            difference <= ELEVATION_TOLERANCE * map_config.vertical_scale,
+            "CPU and GPU noise differ by {}",
+            difference
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with cargo test -- --ignored"]
+    fn matches_gpu_for_each_noise_type() {
+        use config::NoiseType::*;
+        for kind in [Value, Perlin, Simplex, Worley, Ridged].iter() {
+            assert_matches_gpu(vec![layer(*kind, None)]);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with cargo test -- --ignored"]
+    fn matches_gpu_for_masked_stack() {
+        assert_matches_gpu(vec![
+            layer(config::NoiseType::Perlin, None),
+            layer(config::NoiseType::Ridged, Some(0)),
+            layer(config::NoiseType::Worley, Some(1)),
+        ]);
+    }
 }
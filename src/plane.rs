@@ -1,11 +1,10 @@
+use crate::settings;
 use cgmath::*;
 use std::{collections::HashMap, mem};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use wgpu::util::DeviceExt;
 
-use crate::settings;
-
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -224,46 +223,13 @@ fn lod_z_pos(lod: u32, connect_type: &ConnectType) -> u32 {
     }
 }
 
-pub fn get_connect_type(a: Vector3<f32>, b: Vector3<f32>, lod: u32, z_far: f32) -> ConnectType {
-    let ts = settings::TILE_SIZE as f32;
-
-    if get_lod(a, vec3(b.x + ts, 0.0, b.z), z_far) < lod {
-        if get_lod(a, vec3(b.x, 0.0, b.z + ts), z_far) < lod {
-            return ConnectType::XPosZPos;
-        }
-        if get_lod(a, vec3(b.x, 0.0, b.z - ts), z_far) < lod {
-            return ConnectType::XPosZNeg;
-        }
-
-        return ConnectType::XPos;
-    }
-    if get_lod(a, vec3(b.x - ts, 0.0, b.z), z_far) < lod {
-        if get_lod(a, vec3(b.x, 0.0, b.z + ts), z_far) < lod {
-            return ConnectType::XNegZPos;
-        }
-        if get_lod(a, vec3(b.x, 0.0, b.z - ts), z_far) < lod {
-            return ConnectType::XNegZNeg;
-        }
-        return ConnectType::XNeg;
-    }
-    if get_lod(a, vec3(b.x, 0.0, b.z + ts), z_far) < lod {
-        return ConnectType::ZPos;
-    }
-    if get_lod(a, vec3(b.x, 0.0, b.z - ts), z_far) < lod {
-        return ConnectType::ZNeg;
-    }
-
-    ConnectType::None
-}
-
-pub fn get_lod(a: Vector3<f32>, b: Vector3<f32>, z_far: f32) -> u32 {
-    let distance = a.distance(b) / z_far;
-    for i in 0..settings::LODS.len() {
-        let lod = settings::LODS.get(i).expect("Failed to get LOD!");
-        if lod > &distance {
-            return i as u32;
-        }
-    }
+// The distances over which vertices of tiles drawn at the given LOD morph into the next one. Tiles switch LOD by their center
+// so the morph ends a tile radius before the switch and starts a tile radius after the previous one
+pub fn get_morph_range(lod: usize, z_far: f32, tile_size: u32, lods: &[f32]) -> (f32, f32) {
+    let radius = tile_size as f32 * std::f32::consts::FRAC_1_SQRT_2;
+    let previous = if lod > 0 { lods[lod - 1] * z_far + radius } else { 0.0 };
+    let end = lods[lod] * z_far - radius;
+    let start = end - (end - previous).max(0.0) * settings::LOD_MORPH_BAND;
 
-    settings::LODS.len() as u32
+    (start.min(end - 1.0), end)
 }
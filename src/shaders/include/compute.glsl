@@ -1,6 +1,8 @@
 layout(local_size_x = 16, local_size_y = 1) in;
 
 layout(set=0, binding=0) uniform ComputeData {
+    ivec2 u_origin;
+    uvec2 u_extent;
     float u_sea_level;
     float u_horizontal_scale;
     float u_vertical_scale;
@@ -8,10 +10,61 @@ layout(set=0, binding=0) uniform ComputeData {
     uint u_octaves;
     uint u_current_stage;
     uint u_stage_count;
+    uint u_droplet_steps;
+    float u_deposition_rate;
+    float u_erosion_rate;
+    float u_erosion_speed;
+    float u_erosion_friction;
+    float u_talus;
+    float u_thermal_rate;
+    uint u_apron;
+};
+
+#define MAX_NOISE_LAYERS 8
+
+struct NoiseLayer {
+    uint kind;
+    uint octaves;
+    int mask;
+    float frequency;
+    float amplitude;
+    float warp;
+};
+
+layout(set=0, binding=1) uniform NoiseStack {
+    NoiseLayer u_noise_layers[MAX_NOISE_LAYERS];
+    uint u_noise_layer_count;
 };
 
 layout (set = 1, binding = 0, rgba32f) uniform image2D elevation_normal_texture;
 layout (set = 1, binding = 1, rgba32f) uniform image2D biome_texture;
+layout (set = 1, binding = 2, rgba32f) uniform image2D water_texture;
+layout (set = 1, binding = 3, r32f) uniform image2D scratch_texture;
 
 #define NOISE_SET 2
-#include "noise.glsl"
\ No newline at end of file
+#include "noise.glsl"
+
+// The map textures are addressed toroidally so chunks can be streamed in without moving existing data
+bool in_region() {
+    return gl_GlobalInvocationID.x < u_extent.x && gl_GlobalInvocationID.y < u_extent.y;
+}
+
+ivec2 map_position() {
+    return u_origin + ivec2(gl_GlobalInvocationID.xy);
+}
+
+ivec2 texel(ivec2 p) {
+    return ivec2(mod(vec2(p), float(u_size)));
+}
+
+bool in_region(ivec2 p) {
+    ivec2 local = p - u_origin;
+    return local.x >= 0 && local.y >= 0 && local.x < int(u_extent.x) && local.y < int(u_extent.y);
+}
+
+// Stages padded with an apron only write the map textures inside it, the apron is just read to get the borders right
+bool in_interior(ivec2 p) {
+    ivec2 local = p - u_origin;
+    int apron = int(u_apron);
+    return local.x >= apron && local.y >= apron && local.x < int(u_extent.x) - apron && local.y < int(u_extent.y) - apron;
+}
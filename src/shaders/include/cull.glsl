@@ -0,0 +1,96 @@
+layout(local_size_x = 64) in;
+
+layout(set=0, binding=0) uniform CullUniforms {
+    vec4 planes[6];
+    vec4 clip;
+    vec3 lod_origin;
+    float z_far;
+    vec4 lods;
+    uint lod_count;
+    float tile_size;
+    uint node_count;
+    uint max_nodes;
+    mat4 occlusion_view_proj;
+    vec2 depth_size;
+    uint occlusion_levels;
+    uint occlusion;
+} cull;
+
+layout(set=0, binding=1) buffer Stats {
+    uint occluded_nodes;
+    uint occluded_instances;
+} stats;
+
+// Farthest depth of the previous frame, level 0 covers 2x2 pixels
+layout(set=3, binding=0) uniform texture2D t_hiz;
+layout(set=3, binding=1) uniform sampler t_hiz_sampler;
+
+bool box_in_frustum(vec3 bounds_min, vec3 bounds_max) {
+    for (int i = 0; i < 6; i++) {
+        vec4 plane = cull.planes[i];
+        vec3 corner = mix(bounds_max, bounds_min, lessThan(plane.xyz, vec3(0.0)));
+        if (dot(plane.xyz, corner) < -plane.w) {
+            return false;
+        }
+    }
+    return true;
+}
+
+bool sphere_in_frustum(vec4 sphere) {
+    for (int i = 0; i < 6; i++) {
+        vec4 plane = cull.planes[i];
+        if (dot(plane.xyz, sphere.xyz) + plane.w < -sphere.w) {
+            return false;
+        }
+    }
+    return true;
+}
+
+float read_hiz(ivec2 texel, int level) {
+    return texelFetch(sampler2D(t_hiz, t_hiz_sampler), texel, level).x;
+}
+
+// Projects the box with the matrix of the previous frame and compares its nearest depth to the farthest depth behind it,
+// boxes crossing the near plane or the screen edges, or covering too many texels, are never occluded
+bool is_occluded(vec3 bounds_min, vec3 bounds_max) {
+    if (cull.occlusion == 0u) {
+        return false;
+    }
+
+    vec2 ndc_min = vec2(1e30);
+    vec2 ndc_max = vec2(-1e30);
+    float nearest = 1.0;
+    for (int i = 0; i < 8; i++) {
+        vec3 corner = mix(bounds_min, bounds_max, vec3(i & 1, (i >> 1) & 1, (i >> 2) & 1));
+        vec4 clip = cull.occlusion_view_proj * vec4(corner, 1.0);
+        if (clip.w <= 0.0) {
+            return false;
+        }
+
+        vec3 ndc = clip.xyz / clip.w;
+        ndc_min = min(ndc_min, ndc.xy);
+        ndc_max = max(ndc_max, ndc.xy);
+        nearest = min(nearest, ndc.z);
+    }
+
+    if (any(lessThan(ndc_min, vec2(-1.0))) || any(greaterThan(ndc_max, vec2(1.0)))) {
+        return false;
+    }
+
+    vec2 pixel_min = (vec2(ndc_min.x, -ndc_max.y) * 0.5 + 0.5) * cull.depth_size;
+    vec2 pixel_max = (vec2(ndc_max.x, -ndc_min.y) * 0.5 + 0.5) * cull.depth_size;
+    vec2 extent = pixel_max - pixel_min;
+
+    int level = clamp(int(ceil(log2(max(max(extent.x, extent.y), 1.0)))) - 1, 0, int(cull.occlusion_levels) - 1);
+    ivec2 texel_min = ivec2(pixel_min) >> (level + 1);
+    ivec2 texel_max = ivec2(pixel_max) >> (level + 1);
+    if (any(greaterThan(texel_max - texel_min, ivec2(1)))) {
+        return false;
+    }
+
+    float farthest = max(
+        max(read_hiz(texel_min, level), read_hiz(ivec2(texel_max.x, texel_min.y), level)),
+        max(read_hiz(ivec2(texel_min.x, texel_max.y), level), read_hiz(texel_max, level))
+    );
+    return nearest > farthest;
+}
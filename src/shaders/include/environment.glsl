@@ -8,21 +8,100 @@
         #define ENVIRONMENT_TEXTURE_SET 5
     #endif
 
-    #define OFFSET 1.2
-    #define CASCADE_COUNT 4
+    #define MAX_CASCADE_COUNT 4
+    #define MAX_LOD_COUNT 4
+
+    #define SHADOW_PCF 0u
+    #define SHADOW_POISSON 1u
+    #define SHADOW_PCSS 2u
+    #define MAX_PENUMBRA 24.0
 
     layout(set=ENVIRONMENT_SET, binding=0) uniform EnvironmentUniforms {
         vec3 light_dir;
         float ambient_strength;
         vec3 light_color;
         float light_intensity;
-        mat4 shadow_matrix[CASCADE_COUNT];
-        vec4 shadow_split[CASCADE_COUNT];
+        mat4 shadow_matrix[MAX_CASCADE_COUNT];
+        vec4 shadow_split[MAX_CASCADE_COUNT];
+        vec4 lod_morph[MAX_LOD_COUNT];
+        vec3 lod_origin;
+        uint lod_count;
+        vec3 fog_color;
         float time;
+        uint shadow_cascade_count;
+        uint shadow_filter;
+        float shadow_blend;
+        float shadow_filter_radius;
+        float shadow_light_size;
     } env;
 
-    layout(set = ENVIRONMENT_TEXTURE_SET, binding = 0) uniform texture2D t_shadow[3];
+    layout(set = ENVIRONMENT_TEXTURE_SET, binding = 0) uniform texture2D t_shadow[MAX_CASCADE_COUNT];
     layout(set = ENVIRONMENT_TEXTURE_SET, binding = 1) uniform samplerShadow t_shadow_sampler;
+    layout(set = ENVIRONMENT_TEXTURE_SET, binding = 2) uniform sampler t_shadow_depth_sampler;
+
+    const vec2 POISSON_DISK[16] = vec2[](
+        vec2(-0.94201624, -0.39906216), vec2(0.94558609, -0.76890725),
+        vec2(-0.09418410, -0.92938870), vec2(0.34495938, 0.29387760),
+        vec2(-0.91588581, 0.45771432), vec2(-0.81544232, -0.87912464),
+        vec2(-0.38277543, 0.27676845), vec2(0.97484398, 0.75648379),
+        vec2(0.44323325, -0.97511554), vec2(0.53742981, -0.47373420),
+        vec2(-0.26496911, -0.41893023), vec2(0.79197514, 0.19090188),
+        vec2(-0.24188840, 0.99706507), vec2(-0.81409955, 0.91437590),
+        vec2(0.19984126, 0.78641367), vec2(0.14383161, -0.14100790)
+    );
+
+    float sample_pcf(int cascade_index, vec3 light_local, vec2 texel_size) {
+        float total = 0.0;
+        for (float y = -1.0; y <= 1.0; y += 1.0) {
+            for (float x = -1.0; x <= 1.0; x += 1.0) {
+                vec2 offset = vec2(x, y) * texel_size * env.shadow_filter_radius;
+                vec3 uvc = vec3(light_local.xy + offset, light_local.z);
+                total += texture(sampler2DShadow(t_shadow[cascade_index], t_shadow_sampler), uvc);
+            }
+        }
+
+        return total / 9.0;
+    }
+
+    // The disk is rotated per shadow texel so the banding of the few samples turns into noise
+    float sample_poisson(int cascade_index, vec3 light_local, vec2 texel_size, float radius) {
+        vec2 texel = floor(light_local.xy / texel_size);
+        float angle = 6.2831853 * fract(52.9829189 * fract(dot(texel, vec2(0.06711056, 0.00583715))));
+        mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+
+        float total = 0.0;
+        for (int i = 0; i < 16; i++) {
+            vec2 offset = rotation * POISSON_DISK[i] * texel_size * radius;
+            vec3 uvc = vec3(light_local.xy + offset, light_local.z);
+            total += texture(sampler2DShadow(t_shadow[cascade_index], t_shadow_sampler), uvc);
+        }
+
+        return total / 16.0;
+    }
+
+    // The light is orthographic so the penumbra only depends on how far the receiver is behind its blockers,
+    // scaled from depth into texels by the resolution as every cascade spans twice its radius in depth and in width
+    float sample_pcss(int cascade_index, vec3 light_local, vec2 texel_size) {
+        float resolution = 1.0 / texel_size.x;
+        float search = clamp(light_local.z * resolution * env.shadow_light_size, env.shadow_filter_radius, MAX_PENUMBRA);
+
+        float blockers = 0.0;
+        float blocker_depth = 0.0;
+        for (int i = 0; i < 16; i++) {
+            vec2 uv = light_local.xy + POISSON_DISK[i] * texel_size * search;
+            float depth = texture(sampler2D(t_shadow[cascade_index], t_shadow_depth_sampler), uv).r;
+            if (depth < light_local.z) {
+                blockers += 1.0;
+                blocker_depth += depth;
+            }
+        }
+        if (blockers == 0.0) {
+            return 1.0;
+        }
+
+        float penumbra = (light_local.z - blocker_depth / blockers) * resolution * env.shadow_light_size;
+        return sample_poisson(cascade_index, light_local, texel_size, clamp(penumbra, env.shadow_filter_radius, MAX_PENUMBRA));
+    }
 
     float get_shadow_factor(vec3 position, int cascade_index) {
         vec4 shadow_coords = env.shadow_matrix[cascade_index] * vec4(position, 1.0);
@@ -37,23 +116,28 @@
         );
 
         vec2 texel_size = 1.0 / textureSize(sampler2DShadow(t_shadow[cascade_index], t_shadow_sampler), 0);
-        float total = 0.0;
-
-        for (float y = -OFFSET; y <= OFFSET; y += OFFSET) {
-            for (float x = -OFFSET; x <= OFFSET; x += OFFSET) {
-                vec2 offset = vec2(x, y) * texel_size;
-                vec3 uvc = vec3(light_local.xy + offset, light_local.z);
-                total += texture(sampler2DShadow(t_shadow[cascade_index], t_shadow_sampler), uvc);
-            }
+        if (env.shadow_filter == SHADOW_POISSON) {
+            return sample_poisson(cascade_index, light_local, texel_size, env.shadow_filter_radius);
         }
-
-        return total / 9.0;
+        if (env.shadow_filter == SHADOW_PCSS) {
+            return sample_pcss(cascade_index, light_local, texel_size);
+        }
+        return sample_pcf(cascade_index, light_local, texel_size);
     }
 
+    // Cascades are picked by view depth, towards the end of each the next one is blended in and the last one fades out
     float get_shadow(vec3 position) {
-        for(int i = 0; i < CASCADE_COUNT; i ++) {
-            if (distance(cam.eye_pos, position) < env.shadow_split[i].x) {
-                return get_shadow_factor(position, i);
+        float depth = dot(position - cam.eye_pos, normalize(cam.look_at - cam.eye_pos));
+        for (int i = 0; i < env.shadow_cascade_count; i++) {
+            vec4 split = env.shadow_split[i];
+            if (depth < split.x) {
+                float shadow = get_shadow_factor(position, i);
+                float fade = (split.x - depth) / max((split.x - split.y) * env.shadow_blend, 0.0001);
+                if (fade < 1.0) {
+                    float next = i + 1 < env.shadow_cascade_count ? get_shadow_factor(position, i + 1) : 1.0;
+                    shadow = mix(next, shadow, fade);
+                }
+                return shadow;
             }
         }
         return 1.0;
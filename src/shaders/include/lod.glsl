@@ -0,0 +1,25 @@
+#include "environment.glsl"
+
+#ifndef LOD_INITIALIZED
+    // Moves a vertex onto the grid of the next LOD as it gets closer to where its tile switches to that LOD,
+    // the grid of LOD i has a spacing of 2^i so coarser vertices are never moved by the finer LODs
+    vec2 morph_vertex(vec2 pos) {
+        float dist = distance(env.lod_origin, vec3(pos.x, 0.0, pos.y));
+
+        for (uint i = 0u; i < env.lod_count; i++) {
+            vec2 range = env.lod_morph[i].xy;
+            float morph = clamp((dist - range.x) / (range.y - range.x), 0.0, 1.0);
+            pos -= mod(pos, exp2(float(i + 1u))) * morph;
+        }
+
+        return pos;
+    }
+
+    // Bilinear weights of the four texels around a morphed vertex, the texels are ordered (0, 0), (1, 0), (0, 1), (1, 1)
+    vec4 morph_weights(vec2 pos) {
+        vec2 f = fract(pos);
+        return vec4((1.0 - f.x) * (1.0 - f.y), f.x * (1.0 - f.y), (1.0 - f.x) * f.y, f.x * f.y);
+    }
+#endif
+
+#define LOD_INITIALIZED 1
@@ -0,0 +1,29 @@
+#ifndef NODE_INITIALIZED
+    struct Node {
+        vec4 bounds_min;
+        vec4 bounds_max;
+        vec2 translation;
+        float size;
+        uint water;
+    };
+
+    #ifndef NODE_SET
+        layout(set=1, binding=0) readonly buffer Nodes {
+            Node nodes[];
+        };
+    #else
+        // Rendered nodes are picked by the culling pass, the visible list is bound at the offset of the current draw
+        layout(set=NODE_SET, binding=0) readonly buffer Nodes {
+            Node nodes[];
+        };
+        layout(set=NODE_SET, binding=1) readonly buffer Visible {
+            uint visible[];
+        };
+
+        Node get_node() {
+            return nodes[visible[gl_InstanceIndex]];
+        }
+    #endif
+#endif
+
+#define NODE_INITIALIZED 1
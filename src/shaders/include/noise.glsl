@@ -24,6 +24,98 @@ float noise(vec2 st) {
         (d - b) * u.x * u.y;
 }
 
+// Noise types of the elevation noise stack, the values match config::NoiseType
+#define NOISE_VALUE 0u
+#define NOISE_PERLIN 1u
+#define NOISE_SIMPLEX 2u
+#define NOISE_WORLEY 3u
+#define NOISE_RIDGED 4u
+
+vec2 gradient(vec2 i) {
+    float angle = random(i) * 6.2831853;
+    return vec2(cos(angle), sin(angle));
+}
+
+float perlin(vec2 st) {
+    vec2 i = floor(st);
+    vec2 f = st - i;
+    float a = dot(gradient(i), f);
+    float b = dot(gradient(i + vec2(1.0, 0.0)), f - vec2(1.0, 0.0));
+    float c = dot(gradient(i + vec2(0.0, 1.0)), f - vec2(0.0, 1.0));
+    float d = dot(gradient(i + vec2(1.0, 1.0)), f - vec2(1.0, 1.0));
+
+    vec2 u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y) * 0.7071 + 0.5;
+}
+
+float simplex_corner(vec2 x, vec2 g) {
+    float t = max(0.5 - dot(x, x), 0.0);
+    t *= t;
+    return t * t * dot(g, x);
+}
+
+float simplex(vec2 st) {
+    const float F2 = 0.3660254;
+    const float G2 = 0.2113249;
+
+    vec2 i = floor(st + (st.x + st.y) * F2);
+    vec2 x0 = st - i + (i.x + i.y) * G2;
+    vec2 o = x0.x > x0.y ? vec2(1.0, 0.0) : vec2(0.0, 1.0);
+    vec2 x1 = x0 - o + G2;
+    vec2 x2 = x0 - 1.0 + 2.0 * G2;
+
+    float n = simplex_corner(x0, gradient(i)) + simplex_corner(x1, gradient(i + o)) + simplex_corner(x2, gradient(i + 1.0));
+    return n * 35.0 + 0.5;
+}
+
+// Distance to the closest feature point, each cell has one at a random position
+float worley(vec2 st) {
+    vec2 i = floor(st);
+    vec2 f = st - i;
+    float d = 1.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec2 cell = vec2(float(x), float(y));
+            vec2 point = cell + vec2(random(i + cell), random(i + cell + vec2(57.0, 113.0)));
+            d = min(d, length(point - f));
+        }
+    }
+    return d;
+}
+
+float layer_noise(uint kind, vec2 st) {
+    switch (kind) {
+        case NOISE_PERLIN: return perlin(st);
+        case NOISE_SIMPLEX: return simplex(st);
+        case NOISE_WORLEY: return worley(st);
+        default: return noise(st);
+    }
+}
+
+// Same octaves as fbm, ridged noise weights every octave by the one before it
+float layer_fbm(uint kind, vec2 st, uint octaves) {
+    float v = 0.0;
+    float a = 0.5;
+    float weight = 1.0;
+    vec2 shift = vec2(100.0);
+
+    mat2 rot = mat2(cos(0.5), sin(0.5), -sin(0.5), cos(0.5));
+    for (int i = 0; i < octaves; ++i) {
+        float n;
+        if (kind == NOISE_RIDGED) {
+            n = 1.0 - abs(perlin(st) * 2.0 - 1.0);
+            n = n * n * weight;
+            weight = clamp(n * 2.0, 0.0, 1.0);
+        } else {
+            n = layer_noise(kind, st);
+        }
+        v += a * n;
+        st = rot * st * 2.0 + shift;
+        a *= 0.5;
+    }
+    return v;
+}
+
 float fbm(vec2 st, uint octaves) {
     float v = 0.0;
     float a = 0.5;
@@ -4,10 +4,12 @@
 layout(location=0) out vec4 f_color;
 
 layout(set=1, binding=0) uniform SkyUniforms {
-    vec3 light_dir;
-    float not_used;
+    vec3 sun_dir;
+    float sun_visibility;
     vec3 color;
     float fade_distance;
+    vec3 moon_dir;
+    float moon_visibility;
 } sky;
 
 vec4 world_pos_from_depth(float depth, vec2 coords, mat4 view_proj) {
@@ -20,11 +22,13 @@ vec3 sky_color() {
     proj[3][0] = 0.0; proj[3][1] = 0.0; proj[3][2] = 0.0;
     vec3 ray_dir = normalize(world_pos_from_depth(0.6, gl_FragCoord.xy / cam.viewport_size, proj).xyz);
 
-    vec3 sun = pow(max(dot(ray_dir, normalize(-sky.light_dir)), 0.0) * 0.993, 50.0) * vec3(1, 0.7, 0.3);
+    vec3 sun = pow(max(dot(ray_dir, sky.sun_dir), 0.0) * 0.993, 50.0) * vec3(1, 0.7, 0.3) * sky.sun_visibility;
+    vec3 moon = smoothstep(0.9997, 0.9999, dot(ray_dir, sky.moon_dir)) * vec3(0.8, 0.85, 1.0) * sky.moon_visibility;
     float theta = atan(max(ray_dir.y, 0.0) / length(vec2(ray_dir.x, ray_dir.z)));
     float sky_factor = pow(abs(sin(theta)), 0.5);
-    vec3 sky = sky_factor * sky.color + (1.0 - sky_factor) * vec3(1.0, 1.0, 0.9);
-    return pow(sky + sun, vec3(2.2));
+    vec3 horizon = mix(sky.color, vec3(1.0, 1.0, 0.9), sky.sun_visibility);
+    vec3 sky = sky_factor * sky.color + (1.0 - sky_factor) * horizon;
+    return pow(sky + sun + moon, vec3(2.2));
 }
 
 void main() {
@@ -0,0 +1,30 @@
+#version 450
+#include "include/camera.glsl"
+#include "include/lod.glsl"
+
+#define NODE_SET 3
+#include "include/node.glsl"
+
+layout(location=0) in vec2 a_position;
+
+layout(set = 6, binding = 0) uniform texture2D t_elvation_normal;
+layout(set = 6, binding = 2) uniform sampler t_compute_sampler;
+
+float get_elevation(vec2 pos, float size) {
+    ivec2 sample_pos = ivec2(mod(pos + size / 2, size));
+    return texelFetch(sampler2D(t_elvation_normal, t_compute_sampler), sample_pos, 0).x;
+}
+
+void main() {
+    Node node = get_node();
+    vec2 pos = morph_vertex(a_position + node.translation);
+
+    vec2 base = floor(pos);
+    vec4 w = morph_weights(pos);
+    float elev = get_elevation(base, node.size) * w.x
+        + get_elevation(base + vec2(1.0, 0.0), node.size) * w.y
+        + get_elevation(base + vec2(0.0, 1.0), node.size) * w.z
+        + get_elevation(base + vec2(1.0, 1.0), node.size) * w.w;
+
+    gl_Position = cam.view_proj * vec4(pos.x, elev, pos.y, 1.0);
+}
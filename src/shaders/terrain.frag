@@ -19,58 +19,28 @@ layout(location=0) out vec4 f_color;
 layout(set = 1, binding = 0) uniform texture2D t_textures[26];
 layout(set = 1, binding = 1) uniform sampler s_texture;
 
-#define DESERT 0
-#define GRASSLAND 2
-#define SNOW 4
-#define TROPICAL 6
-#define TUNDRA 8
-#define BARREN 10
-#define ROCK 12
-#define CLIFF 14
-#define SAND 16
-#define FLOWERS 18
-#define MUD 20
-#define PEBBLES_GRASS 22
-#define PEBBLES_DESERT 24
-
-#define BIOME_COUNT 5
-#define TEMP_OVERLAP 10.0
-#define MOIST_OVERLAP 0.1
+#define MAX_BIOME_RANGES 8
 
 struct Texture {
    vec3 base_color;
    vec3 normal;
 };
 
-struct Biome {
-    int type;
-    int alt1;
-    int alt2;
-    int cliff;
-    int beach;
-};
-
+// Texture indices are type, alt1, alt2 and cliff, beach holds the dry and wet beach texture
 struct BiomeRange {
-    Biome dry;
-    Biome wet;
+    ivec4 dry;
+    ivec4 wet;
+    ivec2 beach;
     float max_temp;
     float moist_split;
 };
 
-const Biome snow = Biome(SNOW, SNOW, SNOW, ROCK, SNOW);
-const Biome tundra = Biome(TUNDRA, TUNDRA, TUNDRA, ROCK, MUD);
-const Biome barren = Biome(BARREN, BARREN, BARREN, CLIFF, MUD);
-const Biome grassland = Biome(GRASSLAND, FLOWERS, PEBBLES_GRASS, CLIFF, SAND);
-const Biome desert = Biome(DESERT, PEBBLES_DESERT, PEBBLES_DESERT, CLIFF, DESERT);
-const Biome tropical = Biome(TROPICAL, TROPICAL, TROPICAL, CLIFF, DESERT);
-
-const BiomeRange biomes[BIOME_COUNT] = BiomeRange[BIOME_COUNT](
-    BiomeRange(tundra, snow, 0.0, 0.5),
-    BiomeRange(barren, tundra, 5.0, 0.3),
-    BiomeRange(tundra, grassland, 10.0, 0.3),
-    BiomeRange(barren, grassland, 30.0, 0.3),
-    BiomeRange(desert, tropical, 50.0, 0.4)
-);
+layout(set = 1, binding = 2) uniform Biomes {
+    BiomeRange biomes[MAX_BIOME_RANGES];
+    uint biome_count;
+    float temp_overlap;
+    float moist_overlap;
+};
 
 Texture get_texture(int index) {
     vec3 coords = v_position.xyz * 0.25;
@@ -91,36 +61,36 @@ Texture mix_texture(Texture t1, Texture t2, float val) {
 }
 
 Texture get_biome(float temperature, float moisture, float cliff_beach, float alt) {
-    Texture t_norm = get_texture(biomes[0].wet.type);
-    Texture t_cliff = get_texture(biomes[0].wet.cliff);
-    Texture t_decor = get_texture(biomes[0].wet.alt1);
+    Texture t_norm = get_texture(biomes[0].wet.x);
+    Texture t_cliff = get_texture(biomes[0].wet.w);
+    Texture t_decor = get_texture(biomes[0].wet.y);
 
     float min_temp = biomes[0].max_temp;
 
-    for (int i = 1; i < BIOME_COUNT; i ++) {
+    for (int i = 1; i < int(biome_count); i ++) {
         BiomeRange b = biomes[i];
 
-        if (temperature >= min_temp - TEMP_OVERLAP && temperature <= b.max_temp) {
+        if (temperature >= min_temp - temp_overlap && temperature <= b.max_temp) {
             Texture tn;
             Texture tc;
             Texture ta;
 
-            float moist_diff = (moisture - b.moist_split) / MOIST_OVERLAP;
+            float moist_diff = (moisture - b.moist_split) / moist_overlap;
             if (moist_diff <= -1.0) {
-                tn = get_texture(b.dry.type);
-                tc = get_alt_texture(b.dry.cliff, b.dry.beach, cliff_beach);
-                ta = get_alt_texture(b.dry.alt1, b.dry.alt2, alt);
+                tn = get_texture(b.dry.x);
+                tc = get_alt_texture(b.dry.w, b.beach.x, cliff_beach);
+                ta = get_alt_texture(b.dry.y, b.dry.z, alt);
             } else if (moist_diff >= 1.0) {
-                tn = get_texture(b.wet.type);
-                tc = get_alt_texture(b.wet.cliff, b.wet.beach, cliff_beach);
-                ta = get_alt_texture(b.wet.alt1, b.wet.alt2, alt);
+                tn = get_texture(b.wet.x);
+                tc = get_alt_texture(b.wet.w, b.beach.y, cliff_beach);
+                ta = get_alt_texture(b.wet.y, b.wet.z, alt);
             } else {
-                tn = mix_texture(get_texture(b.dry.type), get_texture(b.wet.type), moist_diff * 0.5 + 0.5);
-                tc = mix_texture(get_alt_texture(b.dry.cliff, b.dry.beach, cliff_beach), get_alt_texture(b.wet.cliff, b.wet.beach, cliff_beach), moist_diff * 0.5 + 0.5);
-                ta = mix_texture(get_alt_texture(b.dry.alt1, b.dry.alt2, alt), get_alt_texture(b.wet.alt1, b.wet.alt2, alt), moist_diff * 0.5 + 0.5);
+                tn = mix_texture(get_texture(b.dry.x), get_texture(b.wet.x), moist_diff * 0.5 + 0.5);
+                tc = mix_texture(get_alt_texture(b.dry.w, b.beach.x, cliff_beach), get_alt_texture(b.wet.w, b.beach.y, cliff_beach), moist_diff * 0.5 + 0.5);
+                ta = mix_texture(get_alt_texture(b.dry.y, b.dry.z, alt), get_alt_texture(b.wet.y, b.wet.z, alt), moist_diff * 0.5 + 0.5);
             }
 
-            float temp_diff = (min_temp - temperature) / TEMP_OVERLAP;
+            float temp_diff = (min_temp - temperature) / temp_overlap;
             if (temp_diff > 0.0 && temp_diff < 1.0) {
                 t_norm = mix_texture(tn, t_norm, temp_diff);
                 t_cliff = mix_texture(tc, t_cliff, temp_diff);
@@ -1,5 +1,9 @@
 #version 450
 #include "include/camera.glsl"
+#include "include/lod.glsl"
+
+#define NODE_SET 3
+#include "include/node.glsl"
 
 layout(location=0) in vec2 a_position;
 
@@ -8,30 +12,35 @@ layout(location=1) out mat3 v_tbn;
 layout(location=4) out vec3 v_normal;
 layout(location=5) out float v_size;
 
-layout(set=3, binding=0) uniform Node {
-    vec2 u_translation;
-    float u_size;
-};
-
 layout(set = 6, binding = 0) uniform texture2D t_elvation_normal;
 layout(set = 6, binding = 2) uniform sampler t_compute_sampler;
 
+vec4 get_elevation_normal(vec2 pos, float size) {
+    ivec2 sample_pos = ivec2(mod(pos + size / 2, size));
+    return texelFetch(sampler2D(t_elvation_normal, t_compute_sampler), sample_pos, 0);
+}
+
 void main() {
-    vec2 pos = a_position + u_translation;
+    Node node = get_node();
+    vec2 pos = morph_vertex(a_position + node.translation);
 
-    ivec2 sample_pos = ivec2(pos + (u_size / 2));
-    vec4 elevation_normal = texelFetch(sampler2D(t_elvation_normal, t_compute_sampler), sample_pos, 0);
+    vec2 base = floor(pos);
+    vec4 w = morph_weights(pos);
+    vec4 elevation_normal = get_elevation_normal(base, node.size) * w.x
+        + get_elevation_normal(base + vec2(1.0, 0.0), node.size) * w.y
+        + get_elevation_normal(base + vec2(0.0, 1.0), node.size) * w.z
+        + get_elevation_normal(base + vec2(1.0, 1.0), node.size) * w.w;
 
     float elev = elevation_normal.x;
-    vec3 normal = elevation_normal.yzw;
+    vec3 normal = normalize(elevation_normal.yzw);
 
     vec3 bitangent = normalize(cross(vec3(0.0, 0.0, 1.0), normal));
     vec3 tangent = normalize(cross(normal, bitangent));
 
-    v_position = vec4(a_position.x + u_translation.x, elev, a_position.y + u_translation.y, 1.0);
+    v_position = vec4(pos.x, elev, pos.y, 1.0);
     v_tbn = mat3(tangent, bitangent, normal);
     v_normal = normal;
-    v_size = u_size;
+    v_size = node.size;
 
     gl_ClipDistance[0] = dot(v_position, cam.clip);
     gl_Position = cam.view_proj * v_position;
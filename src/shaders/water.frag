@@ -10,8 +10,11 @@
 #define SURFACE_COLOR vec3(0.236, 0.394, 0.404)
 #define DEPTH_COLOR vec3(0.0039, 0.00196, 0.145)
 #define EXTINCTION vec3(7.0, 30.0, 40.0)
+// Offset of the clip planes of the reflection and refraction views from sea level
+#define SEA_CLIP 1.0
 
 layout(location=0) in vec4 v_position;
+layout(location=1) in float v_surface;
 layout(location=0) out vec4 f_color;
 
 layout(set = 1, binding = 0) uniform texture2D t_depth_texture;
@@ -41,8 +44,10 @@ void main() {
     float log_depth = texture(sampler2D(t_depth_texture, t_sampler), fragCoord).r;
     float depth = water_depth(log_depth) * 0.3;
 
-    vec3 ground = texture(sampler2D(t_refraction, t_sampler), fragCoord).rgb;
-    vec3 reflection = texture(sampler2D(t_reflection, t_sampler), vec2(1.0 - fragCoord.x, fragCoord.y)).rgb;
+    // Reflection and refraction are only rendered around sea level, lakes and rivers above it reflect the fog color
+    bool sea = v_surface < SEA_CLIP;
+    vec3 ground = sea ? texture(sampler2D(t_refraction, t_sampler), fragCoord).rgb : SURFACE_COLOR;
+    vec3 reflection = sea ? texture(sampler2D(t_reflection, t_sampler), vec2(1.0 - fragCoord.x, fragCoord.y)).rgb : env.fog_color;
     vec3 refraction = mix(mix(ground, SURFACE_COLOR, clamp(depth / 2.0, 0.0, 1.0)), DEPTH_COLOR, clamp(depth / EXTINCTION, 0.0, 1.0));
 
     vec3 light = calculate_light(v_position.xyz, normal, 200.0, 2.0, false);
@@ -4,18 +4,39 @@
 #include "include/noise.glsl"
 #include "include/waves.glsl"
 #include "include/camera.glsl"
+#include "include/lod.glsl"
+
+#define NODE_SET 3
+#include "include/node.glsl"
 
 layout(location=0) in vec2 a_position;
 layout(location=0) out vec4 v_position;
+layout(location=1) out float v_surface;
+
+layout(set = 6, binding = 2) uniform sampler t_compute_sampler;
+layout(set = 6, binding = 3) uniform texture2D t_water;
 
-layout(set=3, binding=0) uniform Node {
-    vec2 u_translation;
-};
+// Lakes and rivers are drawn at their own surface height, everything else at sea level
+float get_water_height(vec2 pos, float size) {
+    ivec2 sample_pos = ivec2(mod(pos + size / 2, size));
+    vec4 water = texelFetch(sampler2D(t_water, t_compute_sampler), sample_pos, 0);
+    return water.y > 0.0 ? water.x : 0.0;
+}
 
 void main() {
-    vec2 pos = a_position + u_translation;
+    Node node = get_node();
+    vec2 pos = morph_vertex(a_position + node.translation);
+
+    vec2 base = floor(pos);
+    vec4 w = morph_weights(pos);
+    float height = get_water_height(base, node.size) * w.x
+        + get_water_height(base + vec2(1.0, 0.0), node.size) * w.y
+        + get_water_height(base + vec2(0.0, 1.0), node.size) * w.z
+        + get_water_height(base + vec2(1.0, 1.0), node.size) * w.w;
+
+    v_surface = height;
     v_position = vec4(pos.x, 0.0, pos.y, 1.0);
-    v_position.y = get_wave(v_position.xz);
+    v_position.y = height + get_wave(v_position.xz);
     gl_Position = cam.view_proj * v_position;
 }
 
@@ -1,11 +1,31 @@
-use crate::{anti_aliasing, camera, input::Input, world};
+use crate::{anti_aliasing, camera, config, input::Input, settings, world};
+use cgmath::*;
 use std::time::Instant;
-use winit::window::Window;
+use winit::{event::*, window::Window};
+
+const BRUSH_KEYS: [(VirtualKeyCode, world::Brush); 9] = [
+    (VirtualKeyCode::Key1, world::Brush::Raise),
+    (VirtualKeyCode::Key2, world::Brush::Lower),
+    (VirtualKeyCode::Key3, world::Brush::Flatten),
+    (VirtualKeyCode::Key4, world::Brush::Smooth),
+    (VirtualKeyCode::Key5, world::Brush::Noise),
+    (VirtualKeyCode::Key6, world::Brush::Warm),
+    (VirtualKeyCode::Key7, world::Brush::Cool),
+    (VirtualKeyCode::Key8, world::Brush::Wet),
+    (VirtualKeyCode::Key9, world::Brush::Dry),
+];
+
+// Hours of the simulated day passed per second of holding a scrub key
+const SCRUB_SPEED: f32 = 2.0;
 
 pub struct State {
     pub viewport: camera::Viewport,
     pub input: Input,
     pub anti_aliasing: anti_aliasing::AntiAliasing,
+    pub sculpting: bool,
+    brush: world::Brush,
+    brush_radius: f32,
+    config_path: String,
     surface: wgpu::Surface,
     device: wgpu::Device,
     queue: wgpu::Queue,
@@ -17,7 +37,7 @@ pub struct State {
 }
 
 impl State {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window, config: config::WorldConfig, config_path: &str) -> Self {
         // Device
         let size = window.inner_size();
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
@@ -36,7 +56,8 @@ impl State {
                     label: None,
                     features: wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
                         | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
-                        | wgpu::Features::DEPTH_CLAMPING,
+                        | wgpu::Features::DEPTH_CLAMPING
+                        | (adapter.features() & wgpu::Features::TIMESTAMP_QUERY),
                     limits: wgpu::Limits {
                         max_bind_groups: 7,
                         max_sampled_textures_per_shader_stage: 32,
@@ -49,11 +70,11 @@ impl State {
             .expect("Failed to request device");
 
         // Camera
-        let viewport = camera::Viewport::new(&device, size.width, size.height);
+        let viewport = camera::Viewport::new(&device, size.width, size.height, &config.camera);
         let swap_chain = viewport.create_swap_chain(&device, &surface);
 
         // Drawing
-        let world = world::World::new(&device, &queue, &viewport).await;
+        let world = world::World::new(&device, &queue, &viewport, config, adapter.get_timestamp_period()).await;
         let anti_aliasing = anti_aliasing::AntiAliasing::new(&device, &queue, &viewport);
 
         Self {
@@ -65,6 +86,10 @@ impl State {
             viewport,
             world,
             input: Input::new(),
+            sculpting: false,
+            brush: world::Brush::Raise,
+            brush_radius: 16.0,
+            config_path: config_path.to_string(),
             start_time: Instant::now(),
             last_frame: Instant::now(),
             frame_time: Vec::new(),
@@ -84,6 +109,185 @@ impl State {
         self.world.resize(&self.device, &self.viewport);
     }
 
+    pub fn export_map(&self) {
+        // The seed is free text, anything but letters, digits, dashes and underscores could leave the export directory
+        let name: String = self
+            .world
+            .data
+            .config
+            .map
+            .seed
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let dir = format!("{}/{}", settings::EXPORT_PATH, if name.is_empty() { "map" } else { &name });
+        match self.world.data.map.export(&dir) {
+            Ok(()) => println!("Exported map to {}", dir),
+            Err(error) => println!("Failed to export map: {}", error),
+        }
+    }
+
+    pub fn toggle_sculpting(&mut self) {
+        self.sculpting = !self.sculpting;
+        self.world.end_stroke();
+        println!("Sculpting: {}", if self.sculpting { "on" } else { "off" });
+    }
+
+    // Number keys select the brush, brackets change its radius and holding the left mouse button applies it
+    fn sculpt(&mut self, frame_time: f32) {
+        for (key, brush) in BRUSH_KEYS.iter() {
+            if self.input.keys.contains(key) && self.brush != *brush {
+                self.brush = *brush;
+                println!("Brush: {:?}", self.brush);
+            }
+        }
+        if self.input.keys.contains(&VirtualKeyCode::LBracket) {
+            self.brush_radius = (self.brush_radius - 0.5).max(2.0);
+        }
+        if self.input.keys.contains(&VirtualKeyCode::RBracket) {
+            self.brush_radius = (self.brush_radius + 0.5).min(200.0);
+        }
+
+        if self.input.mouse_buttons.contains(&MouseButton::Left) {
+            let (origin, dir) = self.viewport.cursor_ray(self.input.cursor_position);
+            let hit = self.world.data.map.read().raycast(origin, dir);
+            if let Some(hit) = hit {
+                let center = vec2(hit.position.x, hit.position.z);
+                self.world
+                    .sculpt(&self.queue, self.brush, center, self.brush_radius, frame_time / 1000.0);
+            }
+        } else {
+            self.world.end_stroke();
+        }
+    }
+
+    // Reloads the map config and regenerates the area under the brush with it
+    pub fn regenerate(&mut self) {
+        let (origin, dir) = self.viewport.cursor_ray(self.input.cursor_position);
+        let hit = self.world.data.map.read().raycast(origin, dir);
+        let center = match hit {
+            Some(hit) => vec2(hit.position.x, hit.position.z),
+            None => return println!("Nothing to regenerate"),
+        };
+
+        let radius = vec2(self.brush_radius, self.brush_radius);
+        let result = std::fs::read_to_string(&self.config_path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| config::WorldConfig::from_json(&json))
+            .and_then(|config| {
+                self.world
+                    .regenerate(&self.device, &self.queue, &config.map, center - radius, center + radius)
+            });
+        if let Err(error) = result {
+            println!("Failed to regenerate: {}", error);
+        }
+    }
+
+    // Reloads the shadow settings from the config, the shadow maps are only recreated when their count or size changed
+    pub fn reload_shadows(&mut self) {
+        let result = std::fs::read_to_string(&self.config_path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| config::WorldConfig::from_json(&json));
+        match result {
+            Ok(config) => {
+                self.world.set_shadows(&self.device, &self.viewport, &config.shadows);
+                println!(
+                    "Shadows: {} cascades at {}px, {:?} splits, {:?} filter",
+                    config.shadows.cascades, config.shadows.resolution, config.shadows.split_scheme, config.shadows.filter
+                );
+            }
+            Err(error) => println!("Failed to reload shadows: {}", error),
+        }
+    }
+
+    pub fn toggle_time(&mut self) {
+        let clock = &mut self.world.data.environment.clock;
+        clock.toggle_pause();
+        println!(
+            "Time: {} at {}",
+            if clock.paused { "paused" } else { "running" },
+            clock.display()
+        );
+    }
+
+    pub fn set_time_speed(&mut self, factor: f32) {
+        let clock = &mut self.world.data.environment.clock;
+        clock.set_speed(clock.speed * factor);
+        println!("Time speed: {}x", clock.speed);
+    }
+
+    // Comma and period move the clock backwards and forwards while held, also when it is paused
+    fn scrub_time(&mut self, frame_time: f32) {
+        let mut hours = 0.0;
+        if self.input.keys.contains(&VirtualKeyCode::Comma) {
+            hours -= SCRUB_SPEED * frame_time / 1000.0;
+        }
+        if self.input.keys.contains(&VirtualKeyCode::Period) {
+            hours += SCRUB_SPEED * frame_time / 1000.0;
+        }
+        if hours != 0.0 {
+            self.world.data.environment.clock.scrub(hours);
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if !self.world.undo(&self.queue) {
+            println!("Nothing to undo");
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if !self.world.redo(&self.queue) {
+            println!("Nothing to redo");
+        }
+    }
+
+    pub fn pick(&self) {
+        if self.sculpting {
+            return;
+        }
+
+        let (origin, dir) = self.viewport.cursor_ray(self.input.cursor_position);
+        let map = self.world.data.map.read();
+        match map.raycast(origin, dir) {
+            Some(hit) => {
+                println!(
+                    "Picked ({:.1}, {:.1}, {:.1}) at {:.1}, normal: ({:.2}, {:.2}, {:.2})",
+                    hit.position.x, hit.position.y, hit.position.z, hit.distance, hit.normal.x, hit.normal.y, hit.normal.z,
+                );
+                if let Some(biome) = &hit.biome {
+                    let biomes = &map.biomes;
+                    let weights: Vec<String> = biome
+                        .weights
+                        .iter()
+                        .map(|(biome, weight)| format!("{} {:.2}", biomes.get_name(*biome), weight))
+                        .collect();
+                    println!("Biome: {} ({})", biomes.get_name(biome.biome), weights.join(", "));
+                    println!("Temperature: {:.2}, moisture: {:.2}", biome.temperature, biome.moisture);
+                }
+                let unknown = |value: Option<f32>| value.map_or("-".to_string(), |v| format!("{:.2}", v));
+                println!(
+                    "Slope: {:.1}°, aspect: {:.1}°, curvature: {}",
+                    hit.slope.to_degrees(),
+                    hit.aspect.to_degrees(),
+                    unknown(hit.curvature),
+                );
+                println!(
+                    "Water depth: {:.1}, water distance: {}",
+                    hit.water_depth.unwrap_or(0.0),
+                    unknown(hit.water_distance)
+                );
+            }
+            None => println!("Nothing picked"),
+        }
+    }
+
     fn frame_time(&mut self) -> f32 {
         let avg_count = 30;
         self.frame_time.push(self.last_frame.elapsed().as_micros() as f32 / 1000.0);
@@ -98,6 +302,10 @@ impl State {
     pub fn update(&mut self) {
         let frame_time = self.frame_time();
         self.viewport.update(&self.input, frame_time);
+        if self.sculpting {
+            self.sculpt(frame_time);
+        }
+        self.scrub_time(frame_time);
         self.input.after_update();
         self.world.update(&self.device, &self.queue, &self.viewport, self.start_time);
     }
@@ -111,7 +319,7 @@ impl State {
         self.anti_aliasing
             .execute(&device, &queue, &frame.view, |color_target, depth_target| {
                 let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("refraction") });
-                world.render(&mut encoder, &color_target, &depth_target);
+                world.render(&device, &mut encoder, &color_target, &depth_target);
                 {
                     optick::event!("submit");
                     queue.submit(std::iter::once(encoder.finish()));
@@ -0,0 +1,406 @@
+use super::{node::Node, systems};
+use crate::{camera, config, plane};
+use cgmath::*;
+use std::{collections::HashMap, mem};
+use strum::IntoEnumIterator;
+mod occlusion;
+mod uniforms;
+mod view;
+pub use self::view::ViewCulling;
+
+// Matches local_size_x in cull.glsl
+const WORKGROUP_SIZE: u32 = 64;
+const MIN_INSTANCE_CAPACITY: usize = 1024;
+const DRAW_ARGS_SIZE: wgpu::BufferAddress = mem::size_of::<uniforms::DrawArgs>() as wgpu::BufferAddress;
+
+pub struct TerrainDraw {
+    pub lod: usize,
+    pub connect_type: plane::ConnectType,
+    pub draw_offset: wgpu::BufferAddress,
+    pub visible_offset: wgpu::DynamicOffset,
+}
+
+pub struct AssetDraw<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub draw_offsets: Vec<wgpu::BufferAddress>,
+}
+
+struct AssetInput {
+    name: String,
+    render_distance: f32,
+    first_draw: usize,
+    primitives: usize,
+    capacity: usize,
+    count: u32,
+    buffer: wgpu::Buffer,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl AssetInput {
+    fn allocate(&mut self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, capacity: usize) {
+        let (buffer, bind_group) = create_instances(device, layout, &self.uniforms, capacity);
+        self.capacity = capacity;
+        self.buffer = buffer;
+        self.bind_group = bind_group;
+    }
+}
+
+// The resident leaves and asset instances live on the GPU so every view can cull them and write its own indirect draws,
+// they are only uploaded again when the quadtree has changed
+pub struct Culling {
+    pub render_bind_group_layout: wgpu::BindGroupLayout,
+    pub occlusion: occlusion::Occlusion,
+    pub generation: u64,
+    pub changed: bool,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    node_output_bind_group_layout: wgpu::BindGroupLayout,
+    asset_output_bind_group_layout: wgpu::BindGroupLayout,
+    asset_input_bind_group_layout: wgpu::BindGroupLayout,
+    node_pipeline: wgpu::ComputePipeline,
+    asset_pipeline: wgpu::ComputePipeline,
+    nodes: wgpu::Buffer,
+    node_bind_group: wgpu::BindGroup,
+    node_draws: Vec<uniforms::DrawArgs>,
+    asset_draws: Vec<uniforms::DrawArgs>,
+    assets: Vec<AssetInput>,
+    node_count: u32,
+    max_nodes: u32,
+    map_size: f32,
+    lods: Vec<f32>,
+    tile_size: u32,
+}
+
+impl Culling {
+    pub fn new(
+        device: &wgpu::Device,
+        viewport: &camera::Viewport,
+        config: &config::WorldConfig,
+        lods: &[HashMap<plane::ConnectType, plane::LodBuffer>],
+        assets: &systems::assets::AssetMap,
+        map_size: u32,
+    ) -> Self {
+        let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cull_uniform_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1, wgpu::ShaderStage::COMPUTE, false, false),
+            ],
+        });
+        let node_input_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cull_node_input_bind_group_layout"),
+            entries: &[storage_entry(0, wgpu::ShaderStage::COMPUTE, true, false)],
+        });
+        let node_output_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cull_node_output_bind_group_layout"),
+            entries: &[
+                storage_entry(0, wgpu::ShaderStage::COMPUTE, false, false),
+                storage_entry(1, wgpu::ShaderStage::COMPUTE, false, false),
+            ],
+        });
+        let asset_input_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cull_asset_input_bind_group_layout"),
+            entries: &[
+                storage_entry(0, wgpu::ShaderStage::COMPUTE, true, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let asset_output_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cull_asset_output_bind_group_layout"),
+            entries: &[
+                storage_entry(0, wgpu::ShaderStage::COMPUTE, false, false),
+                storage_entry(1, wgpu::ShaderStage::COMPUTE, false, false),
+            ],
+        });
+
+        // Terrain and water read their node from the visible list of the draw they belong to
+        let render_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("node_bind_group_layout"),
+            entries: &[
+                storage_entry(0, wgpu::ShaderStage::VERTEX, true, false),
+                storage_entry(1, wgpu::ShaderStage::VERTEX, true, true),
+            ],
+        });
+
+        let occlusion = occlusion::Occlusion::new(device, viewport);
+        let node_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("cull_nodes_pipeline_layout"),
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &node_input_bind_group_layout,
+                &node_output_bind_group_layout,
+                &occlusion.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let asset_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("cull_assets_pipeline_layout"),
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &asset_input_bind_group_layout,
+                &asset_output_bind_group_layout,
+                &occlusion.bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let node_module = device.create_shader_module(&wgpu::include_spirv!("../../shaders/compiled/cull-nodes.comp.spv"));
+        let node_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("cull_nodes_pipeline"),
+            layout: Some(&node_pipeline_layout),
+            module: &node_module,
+            entry_point: "main",
+        });
+        let asset_module = device.create_shader_module(&wgpu::include_spirv!("../../shaders/compiled/cull-assets.comp.spv"));
+        let asset_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("cull_assets_pipeline"),
+            layout: Some(&asset_pipeline_layout),
+            module: &asset_module,
+            entry_point: "main",
+        });
+
+        // Dynamic offsets into the visible lists have to be 256 byte aligned
+        let tiles = 2u32.pow(config.map.tile_depth);
+        let max_nodes = (tiles * tiles + 63) / 64 * 64;
+        let nodes = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_nodes"),
+            size: (max_nodes as usize * mem::size_of::<uniforms::NodeUniforms>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let node_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cull_node_input"),
+            layout: &node_input_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: nodes.as_entire_binding(),
+            }],
+        });
+
+        let node_draws = (0..2)
+            .flat_map(|_| lods.iter())
+            .flat_map(|lod| plane::ConnectType::iter().map(move |connect_type| draw_args(lod.get(&connect_type).unwrap().length)))
+            .collect();
+
+        let mut names: Vec<&String> = assets.keys().collect();
+        names.sort();
+        let mut asset_draws = vec![];
+        let assets = names
+            .into_iter()
+            .map(|name| {
+                let asset = assets.get(name).unwrap();
+                let first_draw = asset_draws.len();
+                asset_draws.extend(asset.primitives.iter().map(|mesh| draw_args(mesh.num_elements)));
+
+                let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("cull_asset_uniforms"),
+                    size: mem::size_of::<uniforms::AssetUniforms>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let (buffer, bind_group) = create_instances(device, &asset_input_bind_group_layout, &uniforms, MIN_INSTANCE_CAPACITY);
+                AssetInput {
+                    name: name.clone(),
+                    render_distance: asset.render_distance,
+                    first_draw,
+                    primitives: asset.primitives.len(),
+                    capacity: MIN_INSTANCE_CAPACITY,
+                    count: 0,
+                    buffer,
+                    uniforms,
+                    bind_group,
+                }
+            })
+            .collect();
+
+        Self {
+            render_bind_group_layout,
+            occlusion,
+            generation: 0,
+            changed: true,
+            uniform_bind_group_layout,
+            node_output_bind_group_layout,
+            asset_output_bind_group_layout,
+            asset_input_bind_group_layout,
+            node_pipeline,
+            asset_pipeline,
+            nodes,
+            node_bind_group,
+            node_draws,
+            asset_draws,
+            assets,
+            node_count: 0,
+            max_nodes,
+            map_size: map_size as f32,
+            lods: config.lods.clone(),
+            tile_size: config.map.tile_size,
+        }
+    }
+
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, root_node: &Node, assets: &systems::assets::AssetMap) {
+        optick::event!();
+        let leaves: Vec<&Node> = root_node.get_leaves().into_iter().take(self.max_nodes as usize).collect();
+
+        let nodes: Vec<uniforms::NodeUniforms> = leaves
+            .iter()
+            .filter_map(|node| {
+                let data = node.get_data()?;
+                let (min, max) = (node.bounding_box.min, node.bounding_box.max);
+                Some(uniforms::NodeUniforms {
+                    bounds_min: [min.x, min.y, min.z, 0.0],
+                    bounds_max: [max.x, max.y, max.z, 0.0],
+                    translation: [node.x, node.z],
+                    size: self.map_size,
+                    water: data.water_level.is_some() as u32,
+                })
+            })
+            .collect();
+        self.node_count = nodes.len() as u32;
+        queue.write_buffer(&self.nodes, 0, bytemuck::cast_slice(&nodes));
+
+        for (index, input) in self.assets.iter_mut().enumerate() {
+            let asset = assets.get(&input.name).unwrap();
+            let instances: Vec<uniforms::InstanceUniforms> = leaves
+                .iter()
+                .filter_map(|node| node.get_data()?.asset_instances.get(&input.name))
+                .flatten()
+                .map(|instance| {
+                    let bounding_box = asset.bounding_box.transform(instance.transform);
+                    let center = bounding_box.min.midpoint(bounding_box.max);
+                    uniforms::InstanceUniforms {
+                        transform: instance.transform,
+                        sphere: [center.x, center.y, center.z, bounding_box.min.distance(bounding_box.max) / 2.0],
+                    }
+                })
+                .collect();
+
+            // Views recreate their outputs when the inputs have grown
+            if instances.len() > input.capacity {
+                input.allocate(device, &self.asset_input_bind_group_layout, instances.len().next_power_of_two());
+                self.generation += 1;
+            }
+
+            input.count = instances.len() as u32;
+            queue.write_buffer(&input.buffer, 0, bytemuck::cast_slice(&instances));
+            queue.write_buffer(
+                &input.uniforms,
+                0,
+                bytemuck::bytes_of(&uniforms::AssetUniforms {
+                    count: input.count,
+                    render_distance: input.render_distance,
+                    index: index as u32,
+                    padding: 0,
+                }),
+            );
+        }
+
+        self.changed = false;
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, viewport: &camera::Viewport) {
+        self.occlusion.resize(device, viewport);
+    }
+
+    // Terrain draws come first and are followed by the water draws, both ordered by LOD and connect type
+    pub fn terrain_draws(&self, water: bool) -> Vec<TerrainDraw> {
+        let lods = self.lods.len() + 1;
+        (0..lods)
+            .flat_map(|lod| plane::ConnectType::iter().map(move |connect_type| (lod, connect_type)))
+            .enumerate()
+            .map(|(i, (lod, connect_type))| {
+                let draw = if water { lods * plane::ConnectType::iter().count() + i } else { i };
+                TerrainDraw {
+                    lod,
+                    connect_type,
+                    draw_offset: draw as wgpu::BufferAddress * DRAW_ARGS_SIZE,
+                    visible_offset: draw as u32 * self.max_nodes * mem::size_of::<u32>() as u32,
+                }
+            })
+            .collect()
+    }
+
+    pub fn asset_draws(&self) -> Vec<AssetDraw<'_>> {
+        self.assets
+            .iter()
+            .enumerate()
+            .map(|(index, input)| AssetDraw {
+                index,
+                name: &input.name,
+                draw_offsets: (0..input.primitives)
+                    .map(|p| (input.first_draw + p) as wgpu::BufferAddress * DRAW_ARGS_SIZE)
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+fn create_instances(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniforms: &wgpu::Buffer,
+    capacity: usize,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("cull_instances"),
+        size: (capacity * mem::size_of::<uniforms::InstanceUniforms>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("cull_asset_input"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: uniforms.as_entire_binding(),
+            },
+        ],
+    });
+    (buffer, bind_group)
+}
+
+fn draw_args(index_count: u32) -> uniforms::DrawArgs {
+    uniforms::DrawArgs {
+        index_count,
+        instance_count: 0,
+        first_index: 0,
+        base_vertex: 0,
+        first_instance: 0,
+    }
+}
+
+fn storage_entry(binding: u32, visibility: wgpu::ShaderStage, read_only: bool, has_dynamic_offset: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
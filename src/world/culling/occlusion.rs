@@ -0,0 +1,242 @@
+use crate::{camera, texture};
+
+// Matches local_size_x and local_size_y in hiz.comp
+const WORKGROUP_SIZE: u32 = 8;
+
+// A max depth pyramid of the last rendered eye view, the culling pass tests against it with the matrix that frame was drawn with
+pub struct Occlusion {
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub ready: bool,
+    pub depth_size: [f32; 2],
+    pub levels: u32,
+    depth_bind_group_layout: wgpu::BindGroupLayout,
+    reduce_bind_group_layout: wgpu::BindGroupLayout,
+    depth_pipeline: wgpu::ComputePipeline,
+    reduce_pipeline: wgpu::ComputePipeline,
+    sampler: wgpu::Sampler,
+    level_views: Vec<(wgpu::TextureView, u32, u32)>,
+    reduce_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Occlusion {
+    pub fn new(device: &wgpu::Device, viewport: &camera::Viewport) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hiz_bind_group_layout"),
+            entries: &[
+                texture_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                sampler_entry(1),
+            ],
+        });
+        let depth_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hiz_depth_bind_group_layout"),
+            entries: &[
+                texture_entry(0, wgpu::TextureSampleType::Depth),
+                sampler_entry(1),
+                output_entry(2),
+            ],
+        });
+        let reduce_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hiz_reduce_bind_group_layout"),
+            entries: &[
+                texture_entry(0, wgpu::TextureSampleType::Float { filterable: false }),
+                sampler_entry(1),
+                output_entry(2),
+            ],
+        });
+
+        let module = device.create_shader_module(&wgpu::include_spirv!("../../shaders/compiled/hiz.comp.spv"));
+        let create_pipeline = |label: &str, layout: &wgpu::BindGroupLayout| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "main",
+            })
+        };
+        let depth_pipeline = create_pipeline("hiz_depth_pipeline", &depth_bind_group_layout);
+        let reduce_pipeline = create_pipeline("hiz_reduce_pipeline", &reduce_bind_group_layout);
+        let sampler = texture::create_sampler(device, wgpu::AddressMode::ClampToEdge, wgpu::FilterMode::Nearest);
+
+        let (bind_group, level_views, reduce_bind_groups, levels) =
+            create_pyramid(device, viewport, &bind_group_layout, &reduce_bind_group_layout, &sampler);
+
+        Self {
+            bind_group_layout,
+            bind_group,
+            ready: false,
+            depth_size: [viewport.width as f32, viewport.height as f32],
+            levels,
+            depth_bind_group_layout,
+            reduce_bind_group_layout,
+            depth_pipeline,
+            reduce_pipeline,
+            sampler,
+            level_views,
+            reduce_bind_groups,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, viewport: &camera::Viewport) {
+        let (bind_group, level_views, reduce_bind_groups, levels) = create_pyramid(
+            device,
+            viewport,
+            &self.bind_group_layout,
+            &self.reduce_bind_group_layout,
+            &self.sampler,
+        );
+
+        self.bind_group = bind_group;
+        self.level_views = level_views;
+        self.reduce_bind_groups = reduce_bind_groups;
+        self.levels = levels;
+        self.depth_size = [viewport.width as f32, viewport.height as f32];
+        self.ready = false;
+    }
+
+    pub fn build(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, depth_target: &wgpu::TextureView) {
+        optick::event!();
+        let (level_view, width, height) = &self.level_views[0];
+        let depth_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("hiz_depth"),
+            layout: &self.depth_bind_group_layout,
+            entries: &[
+                texture::create_bind_group_entry(0, depth_target),
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                texture::create_bind_group_entry(2, level_view),
+            ],
+        });
+
+        // Every level reads the one before it so each gets a pass of its own
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("hiz_depth") });
+            pass.set_pipeline(&self.depth_pipeline);
+            pass.set_bind_group(0, &depth_bind_group, &[]);
+            pass.dispatch(dispatch_size(*width), dispatch_size(*height), 1);
+        }
+
+        for (bind_group, (_, width, height)) in self.reduce_bind_groups.iter().zip(self.level_views.iter().skip(1)) {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("hiz_reduce") });
+            pass.set_pipeline(&self.reduce_pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch(dispatch_size(*width), dispatch_size(*height), 1);
+        }
+    }
+}
+
+fn create_pyramid(
+    device: &wgpu::Device,
+    viewport: &camera::Viewport,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    reduce_bind_group_layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+) -> (wgpu::BindGroup, Vec<(wgpu::TextureView, u32, u32)>, Vec<wgpu::BindGroup>, u32) {
+    // Power of two sizes keep every texel covering exactly four texels of the level below
+    let width = ((viewport.width + 1) / 2).next_power_of_two();
+    let height = ((viewport.height + 1) / 2).next_power_of_two();
+    let levels = 32 - width.max(height).leading_zeros();
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("hiz"),
+        size: wgpu::Extent3d { width, height, depth: 1 },
+        mip_level_count: levels,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R32Float,
+        usage: wgpu::TextureUsage::STORAGE | wgpu::TextureUsage::SAMPLED,
+    });
+
+    let level_views: Vec<(wgpu::TextureView, u32, u32)> = (0..levels)
+        .map(|level| {
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                level_count: std::num::NonZeroU32::new(1),
+                ..Default::default()
+            });
+            (view, (width >> level).max(1), (height >> level).max(1))
+        })
+        .collect();
+
+    let reduce_bind_groups = level_views
+        .windows(2)
+        .map(|views| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("hiz_reduce"),
+                layout: reduce_bind_group_layout,
+                entries: &[
+                    texture::create_bind_group_entry(0, &views[0].0),
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    texture::create_bind_group_entry(2, &views[1].0),
+                ],
+            })
+        })
+        .collect();
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("hiz"),
+        layout: bind_group_layout,
+        entries: &[
+            texture::create_bind_group_entry(0, &view),
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+
+    (bind_group, level_views, reduce_bind_groups, levels)
+}
+
+fn texture_entry(binding: u32, sample_type: wgpu::TextureSampleType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            sample_type,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::Sampler {
+            comparison: false,
+            filtering: false,
+        },
+        count: None,
+    }
+}
+
+fn output_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            view_dimension: wgpu::TextureViewDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            access: wgpu::StorageTextureAccess::WriteOnly,
+        },
+        count: None,
+    }
+}
+
+fn dispatch_size(size: u32) -> u32 {
+    (size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE
+}
@@ -0,0 +1,61 @@
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uniforms {
+    pub planes: [[f32; 4]; 6],
+    pub clip: [f32; 4],
+    pub lod_origin: [f32; 3],
+    pub z_far: f32,
+    pub lods: [f32; 4],
+    pub lod_count: u32,
+    pub tile_size: f32,
+    pub node_count: u32,
+    pub max_nodes: u32,
+    pub occlusion_view_proj: [[f32; 4]; 4],
+    pub depth_size: [f32; 2],
+    pub occlusion_levels: u32,
+    pub occlusion: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Stats {
+    pub occluded_nodes: u32,
+    pub occluded_instances: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct NodeUniforms {
+    pub bounds_min: [f32; 4],
+    pub bounds_max: [f32; 4],
+    pub translation: [f32; 2],
+    pub size: f32,
+    pub water: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceUniforms {
+    pub transform: [[f32; 4]; 4],
+    pub sphere: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AssetUniforms {
+    pub count: u32,
+    pub render_distance: f32,
+    pub index: u32,
+    pub padding: u32,
+}
+
+// Laid out as the arguments of draw_indexed_indirect
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
@@ -0,0 +1,302 @@
+use super::{uniforms, Culling, DRAW_ARGS_SIZE, WORKGROUP_SIZE};
+use crate::{camera, logger, settings, world::enivornment};
+use cgmath::*;
+use futures::{Future, FutureExt};
+use std::{mem, pin::Pin};
+
+type Mapping = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+// The culling results of a single view, the indirect draws of its bundles point into these buffers
+pub struct ViewCulling {
+    pub node_draws: wgpu::Buffer,
+    pub asset_draws: wgpu::Buffer,
+    pub render_bind_group: wgpu::BindGroup,
+    pub asset_outputs: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    uniforms: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    node_bind_group: wgpu::BindGroup,
+    asset_counts: wgpu::Buffer,
+    stats: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    mapping: Option<Mapping>,
+    reporting: bool,
+    generation: Option<u64>,
+}
+
+impl ViewCulling {
+    pub fn new(device: &wgpu::Device, culling: &Culling) -> Self {
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_uniforms"),
+            size: mem::size_of::<uniforms::Uniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let stats_size = mem::size_of::<uniforms::Stats>() as wgpu::BufferAddress;
+        let stats = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_stats"),
+            size: stats_size,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_SRC | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_stats_readback"),
+            size: stats_size,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cull_uniforms"),
+            layout: &culling.uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: stats.as_entire_binding(),
+                },
+            ],
+        });
+
+        let visible_size = culling.max_nodes as wgpu::BufferAddress * mem::size_of::<u32>() as wgpu::BufferAddress;
+        let visible = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_visible"),
+            size: culling.node_draws.len() as wgpu::BufferAddress * visible_size,
+            usage: wgpu::BufferUsage::STORAGE,
+            mapped_at_creation: false,
+        });
+        let node_draws = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_node_draws"),
+            size: culling.node_draws.len() as wgpu::BufferAddress * DRAW_ARGS_SIZE,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::INDIRECT | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let node_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cull_node_output"),
+            layout: &culling.node_output_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: visible.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: node_draws.as_entire_binding(),
+                },
+            ],
+        });
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("node_bind_group"),
+            layout: &culling.render_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: culling.nodes.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Buffer {
+                        buffer: &visible,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(visible_size),
+                    },
+                },
+            ],
+        });
+
+        let asset_draws = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_asset_draws"),
+            size: culling.asset_draws.len().max(1) as wgpu::BufferAddress * DRAW_ARGS_SIZE,
+            usage: wgpu::BufferUsage::INDIRECT | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let asset_counts = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_asset_counts"),
+            size: (culling.assets.len().max(1) * mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut view = Self {
+            node_draws,
+            asset_draws,
+            render_bind_group,
+            asset_outputs: vec![],
+            uniforms,
+            uniform_bind_group,
+            node_bind_group,
+            asset_counts,
+            stats,
+            readback,
+            mapping: None,
+            reporting: false,
+            generation: None,
+        };
+        view.prepare(device, culling);
+        view
+    }
+
+    // Returns true when the asset outputs were recreated and the bundles drawing them have to be recorded again
+    pub fn prepare(&mut self, device: &wgpu::Device, culling: &Culling) -> bool {
+        if self.generation == Some(culling.generation) {
+            return false;
+        }
+
+        let asset_counts = &self.asset_counts;
+        self.asset_outputs = culling
+            .assets
+            .iter()
+            .map(|input| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("cull_asset_transforms"),
+                    size: (input.capacity * mem::size_of::<[[f32; 4]; 4]>()) as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::VERTEX,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("cull_asset_output"),
+                    layout: &culling.asset_output_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: asset_counts.as_entire_binding(),
+                        },
+                    ],
+                });
+                (buffer, bind_group)
+            })
+            .collect();
+
+        self.generation = Some(culling.generation);
+        true
+    }
+
+    // Views passing the matrix their last frame was drawn with also skip what was hidden behind its depth
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        culling: &Culling,
+        frustum: &camera::FrustumCuller,
+        clip: [f32; 4],
+        environment: &enivornment::Environment,
+        occluder: Option<Matrix4<f32>>,
+    ) {
+        let occluder = occluder.filter(|_| culling.occlusion.ready);
+        self.reporting = occluder.is_some();
+        if self.reporting {
+            self.report(device);
+        }
+
+        let mut lods = [0.0; settings::MAX_LODS];
+        for (i, lod) in culling.lods.iter().enumerate() {
+            lods[i] = *lod;
+        }
+
+        queue.write_buffer(
+            &self.uniforms,
+            0,
+            bytemuck::bytes_of(&uniforms::Uniforms {
+                planes: frustum.planes(),
+                clip,
+                lod_origin: environment.lod_origin.into(),
+                z_far: environment.lod_z_far,
+                lods,
+                lod_count: culling.lods.len() as u32,
+                tile_size: culling.tile_size as f32,
+                node_count: culling.node_count,
+                max_nodes: culling.max_nodes,
+                occlusion_view_proj: occluder.unwrap_or(Matrix4::identity()).into(),
+                depth_size: culling.occlusion.depth_size,
+                occlusion_levels: culling.occlusion.levels,
+                occlusion: occluder.is_some() as u32,
+            }),
+        );
+
+        // The culling pass counts the instances of every draw up from zero
+        queue.write_buffer(&self.node_draws, 0, bytemuck::cast_slice(&culling.node_draws));
+        queue.write_buffer(&self.asset_draws, 0, bytemuck::cast_slice(&culling.asset_draws));
+        queue.write_buffer(&self.asset_counts, 0, bytemuck::cast_slice(&vec![0u32; culling.assets.len()]));
+        queue.write_buffer(
+            &self.stats,
+            0,
+            bytemuck::bytes_of(&uniforms::Stats {
+                occluded_nodes: 0,
+                occluded_instances: 0,
+            }),
+        );
+    }
+
+    // The stats are copied out by a frame that is not waiting on a mapping and read back once the mapping completes
+    fn report(&mut self, device: &wgpu::Device) {
+        match self.mapping.as_mut() {
+            None => self.mapping = Some(Box::pin(self.readback.slice(..).map_async(wgpu::MapMode::Read))),
+            Some(mapping) => {
+                device.poll(wgpu::Maintain::Poll);
+                if let Some(result) = mapping.now_or_never() {
+                    if result.is_ok() {
+                        let stats: uniforms::Stats = *bytemuck::from_bytes(&self.readback.slice(..).get_mapped_range());
+                        logger::count("Culling: occluded nodes", stats.occluded_nodes as u64);
+                        logger::count("Culling: occluded instances", stats.occluded_instances as u64);
+                    }
+                    self.readback.unmap();
+                    self.mapping = None;
+                }
+            }
+        }
+    }
+
+    pub fn cull(&self, encoder: &mut wgpu::CommandEncoder, culling: &Culling) {
+        optick::event!();
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("culling") });
+            pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            pass.set_bind_group(3, &culling.occlusion.bind_group, &[]);
+
+            if culling.node_count > 0 {
+                pass.set_pipeline(&culling.node_pipeline);
+                pass.set_bind_group(1, &culling.node_bind_group, &[]);
+                pass.set_bind_group(2, &self.node_bind_group, &[]);
+                pass.dispatch(dispatch_size(culling.node_count), 1, 1);
+            }
+
+            pass.set_pipeline(&culling.asset_pipeline);
+            for (input, (_, bind_group)) in culling.assets.iter().zip(self.asset_outputs.iter()) {
+                if input.count > 0 {
+                    pass.set_bind_group(1, &input.bind_group, &[]);
+                    pass.set_bind_group(2, bind_group, &[]);
+                    pass.dispatch(dispatch_size(input.count), 1, 1);
+                }
+            }
+        }
+
+        // Every primitive of an asset draws the same culled instances
+        let count_size = mem::size_of::<u32>() as wgpu::BufferAddress;
+        for (i, input) in culling.assets.iter().enumerate() {
+            for p in 0..input.primitives {
+                let draw = (input.first_draw + p) as wgpu::BufferAddress;
+                encoder.copy_buffer_to_buffer(
+                    &self.asset_counts,
+                    i as wgpu::BufferAddress * count_size,
+                    &self.asset_draws,
+                    draw * DRAW_ARGS_SIZE + count_size,
+                    count_size,
+                );
+            }
+        }
+
+        if self.reporting && self.mapping.is_none() {
+            let size = mem::size_of::<uniforms::Stats>() as wgpu::BufferAddress;
+            encoder.copy_buffer_to_buffer(&self.stats, 0, &self.readback, 0, size);
+        }
+    }
+}
+
+fn dispatch_size(count: u32) -> u32 {
+    (count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE
+}
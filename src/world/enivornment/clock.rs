@@ -0,0 +1,111 @@
+use crate::config;
+use cgmath::*;
+use std::f32::consts::PI;
+
+const MIN_SPEED: f32 = 0.125;
+const MAX_SPEED: f32 = 256.0;
+
+// The lighting of the current time of day, the sun and moon directions point towards them
+#[derive(Copy, Clone)]
+pub struct Daylight {
+    pub light_dir: Vector3<f32>,
+    pub light_color: Vector3<f32>,
+    pub light_intensity: f32,
+    pub ambient: f32,
+    pub sky_color: Vector3<f32>,
+    pub sun_dir: Vector3<f32>,
+    pub sun_visibility: f32,
+    pub moon_dir: Vector3<f32>,
+    pub moon_visibility: f32,
+}
+
+// Counts the hours of a simulated day, a disabled clock keeps the sun where the light config puts it
+pub struct Clock {
+    pub hour: f32,
+    pub speed: f32,
+    pub paused: bool,
+    light: config::LightConfig,
+    time: config::TimeConfig,
+}
+
+impl Clock {
+    pub fn new(light: &config::LightConfig, time: &config::TimeConfig) -> Self {
+        Self {
+            hour: time.start_hour,
+            speed: 1.0,
+            paused: false,
+            light: light.clone(),
+            time: time.clone(),
+        }
+    }
+
+    pub fn update(&mut self, seconds: f32) {
+        if self.time.enabled && !self.paused {
+            self.scrub(seconds / self.time.day_length * 24.0 * self.speed);
+        }
+    }
+
+    pub fn scrub(&mut self, hours: f32) {
+        self.hour = (self.hour + hours).rem_euclid(24.0);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(MIN_SPEED).min(MAX_SPEED);
+    }
+
+    pub fn display(&self) -> String {
+        let minutes = (self.hour * 60.0) as u32;
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+
+    pub fn daylight(&self) -> Daylight {
+        let sun_dir = if self.time.enabled {
+            sun_dir(self.hour, Deg(self.time.latitude).into())
+        } else {
+            -self.light.dir.normalize()
+        };
+        // A full moon, always opposite the sun
+        let moon_dir = -sun_dir;
+
+        let sun_visibility = smoothstep(0.0, 0.1, sun_dir.y);
+        let moon_visibility = smoothstep(0.0, 0.1, moon_dir.y);
+        let day = smoothstep(-0.1, 0.2, sun_dir.y);
+        let sunset = 1.0 - smoothstep(0.0, 0.3, sun_dir.y.abs());
+
+        // Each light fades out before it reaches the horizon, so the shadows switch between them while neither is visible
+        let (light_dir, light_color, light_intensity) = if sun_dir.y >= 0.0 {
+            let color = self.light.sunset_color.lerp(self.light.color, smoothstep(0.0, 0.3, sun_dir.y));
+            (-sun_dir, color, self.light.intensity * sun_visibility)
+        } else {
+            (-moon_dir, self.light.moon_color, self.light.moon_intensity * moon_visibility)
+        };
+
+        let sky_color = self.light.night_sky_color.lerp(self.light.sky_color, day);
+        Daylight {
+            light_dir,
+            light_color,
+            light_intensity,
+            ambient: self.light.night_ambient + (self.light.ambient - self.light.night_ambient) * day,
+            sky_color: sky_color.lerp(self.light.sunset_color, sunset * 0.5),
+            sun_dir,
+            sun_visibility,
+            moon_dir,
+            moon_visibility,
+        }
+    }
+}
+
+// The hour angle turns the sun around the polar axis, x points east and z south
+fn sun_dir(hour: f32, latitude: Rad<f32>) -> Vector3<f32> {
+    let angle = (hour - 12.0) / 24.0 * 2.0 * PI;
+    vec3(-angle.sin(), latitude.cos() * angle.cos(), latitude.sin() * angle.cos())
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
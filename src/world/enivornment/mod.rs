@@ -1,8 +1,22 @@
-use crate::{camera, settings, texture};
+use crate::{camera, config, plane, settings, texture};
+mod clock;
 mod uniforms;
 use cgmath::*;
+pub use clock::Daylight;
 use std::{convert::TryInto, time::Instant};
 
+// Shadows are cast up to this fraction of the far plane
+const SHADOW_DISTANCE: f32 = 0.65;
+
+// The sphere and light a cascade was last rendered with
+#[derive(Copy, Clone)]
+struct Cascade {
+    center: Vector3<f32>,
+    radius: f32,
+    light_dir: Vector3<f32>,
+    age: u32,
+}
+
 pub struct Environment {
     pub texture_bind_group: wgpu::BindGroup,
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
@@ -10,27 +24,33 @@ pub struct Environment {
     pub uniforms: uniforms::UniformBuffer,
     pub shadow_matrix: Vec<Matrix4<f32>>,
     pub shadow_texture_view: Vec<wgpu::TextureView>,
+    pub shadow_updated: Vec<bool>,
+    pub lod_origin: Vector3<f32>,
+    pub lod_z_far: f32,
+    pub clock: clock::Clock,
+    pub daylight: clock::Daylight,
+    last_update: Instant,
+    shadows: config::ShadowConfig,
+    shadow_sampler: wgpu::Sampler,
+    depth_sampler: wgpu::Sampler,
+    cascades: Vec<Option<Cascade>>,
+    lods: Vec<f32>,
+    tile_size: u32,
 }
 
 impl Environment {
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: &wgpu::Device, config: &config::WorldConfig) -> Self {
+        let cascade_count = config.shadows.cascades as usize;
+        let clock = clock::Clock::new(&config.light, &config.time);
+        let daylight = clock.daylight();
+
         // Textures
         let shadow_sampler = texture::create_shadow_sampler(device);
-        let shadow_texture_view: Vec<wgpu::TextureView> = (0..settings::SHADOW_CASCADE_SPLITS.len())
-            .map(|_| {
-                texture::create_view(
-                    &device,
-                    settings::SHADOW_RESOLUTION,
-                    settings::SHADOW_RESOLUTION,
-                    settings::DEPTH_TEXTURE_FORMAT,
-                )
-            })
-            .collect();
-
+        let depth_sampler = texture::create_sampler(device, wgpu::AddressMode::ClampToEdge, wgpu::FilterMode::Nearest);
         let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("deferred_textures_bind_group_layout"),
             entries: &[
-                texture::create_array_bind_group_layout(0, wgpu::TextureSampleType::Depth, settings::SHADOW_CASCADE_SPLITS.len()),
+                texture::create_array_bind_group_layout(0, wgpu::TextureSampleType::Depth, settings::MAX_SHADOW_CASCADES),
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStage::FRAGMENT,
@@ -40,26 +60,24 @@ impl Environment {
                     },
                     count: None,
                 },
-            ],
-        });
-
-        let t: &[&wgpu::TextureView; settings::SHADOW_CASCADE_SPLITS.len()] =
-            &shadow_texture_view.iter().collect::<Vec<_>>().try_into().unwrap();
-
-        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("deferred_textures"),
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureViewArray(t),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&shadow_sampler),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: false,
+                    },
+                    count: None,
                 },
             ],
         });
+        let (shadow_texture_view, texture_bind_group) = create_shadow_maps(
+            device,
+            &texture_bind_group_layout,
+            &config.shadows,
+            &shadow_sampler,
+            &depth_sampler,
+        );
 
         // Uniforms
         let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -80,13 +98,23 @@ impl Environment {
             &device,
             &uniform_bind_group_layout,
             uniforms::Uniforms {
-                light_dir: settings::LIGHT_DIR.into(),
-                light_color: settings::LIGHT_COLOR.into(),
-                ambient_strength: settings::LIGHT_AMBIENT,
-                light_intensity: settings::LIGHT_INTENSITY,
-                shadow_matrix: [Matrix4::identity().into(); settings::SHADOW_CASCADE_SPLITS.len()],
-                shadow_split_depth: [[0.0, 0.0, 0.0, 0.0]; settings::SHADOW_CASCADE_SPLITS.len()],
+                light_dir: daylight.light_dir.into(),
+                light_color: daylight.light_color.into(),
+                ambient_strength: daylight.ambient,
+                light_intensity: daylight.light_intensity,
+                shadow_matrix: [Matrix4::identity().into(); settings::MAX_SHADOW_CASCADES],
+                shadow_split_depth: [[0.0, 0.0, 0.0, 0.0]; settings::MAX_SHADOW_CASCADES],
+                lod_morph: [[0.0, 0.0, 0.0, 0.0]; settings::MAX_LODS],
+                lod_origin: [0.0, 0.0, 0.0],
+                lod_count: config.lods.len() as u32,
+                fog_color: daylight.sky_color.into(),
                 time: 0.0,
+                shadow_cascade_count: cascade_count as u32,
+                shadow_filter: config.shadows.filter as u32,
+                shadow_blend: config.shadows.blend,
+                shadow_filter_radius: config.shadows.filter_radius,
+                shadow_light_size: config.shadows.light_size,
+                padding: [0.0; 3],
             },
         );
 
@@ -96,16 +124,65 @@ impl Environment {
             uniform_bind_group_layout,
             uniforms,
             shadow_texture_view,
-            shadow_matrix: vec![Matrix4::identity(); settings::SHADOW_CASCADE_SPLITS.len()],
+            shadow_matrix: vec![Matrix4::identity(); cascade_count],
+            shadow_updated: vec![true; cascade_count],
+            cascades: vec![None; cascade_count],
+            lod_origin: Vector3::zero(),
+            lod_z_far: 0.0,
+            clock,
+            daylight,
+            last_update: Instant::now(),
+            shadows: config.shadows.clone(),
+            shadow_sampler,
+            depth_sampler,
+            lods: config.lods.clone(),
+            tile_size: config.map.tile_size,
         }
     }
 
+    // Returns true when the shadow maps were recreated and everything drawing with them has to be recorded again
+    pub fn set_shadows(&mut self, device: &wgpu::Device, shadows: &config::ShadowConfig) -> bool {
+        let recreate = shadows.resolution != self.shadows.resolution || shadows.cascades != self.shadows.cascades;
+        if recreate {
+            let (shadow_texture_view, texture_bind_group) = create_shadow_maps(
+                device,
+                &self.texture_bind_group_layout,
+                shadows,
+                &self.shadow_sampler,
+                &self.depth_sampler,
+            );
+            self.shadow_texture_view = shadow_texture_view;
+            self.texture_bind_group = texture_bind_group;
+            self.shadow_matrix = vec![Matrix4::identity(); shadows.cascades as usize];
+            self.shadow_updated = vec![true; shadows.cascades as usize];
+        }
+
+        self.shadows = shadows.clone();
+        self.cascades = vec![None; shadows.cascades as usize];
+        self.uniforms.data.shadow_cascade_count = shadows.cascades;
+        self.uniforms.data.shadow_filter = shadows.filter as u32;
+        self.uniforms.data.shadow_blend = shadows.blend;
+        self.uniforms.data.shadow_filter_radius = shadows.filter_radius;
+        self.uniforms.data.shadow_light_size = shadows.light_size;
+        recreate
+    }
+
     pub fn update(&mut self, queue: &wgpu::Queue, viewport: &camera::Viewport, view: Matrix4<f32>, time: Instant) {
+        self.clock.update(self.last_update.elapsed().as_secs_f32());
+        self.last_update = Instant::now();
+        self.daylight = self.clock.daylight();
+        self.uniforms.data.light_dir = self.daylight.light_dir.into();
+        self.uniforms.data.light_color = self.daylight.light_color.into();
+        self.uniforms.data.light_intensity = self.daylight.light_intensity;
+        self.uniforms.data.ambient_strength = self.daylight.ambient;
+        self.uniforms.data.fog_color = self.daylight.sky_color.into();
+
         let inv_cam = (viewport.proj * view).inverse_transform().unwrap();
-        let clip_range = (viewport.z_far * 0.65) - viewport.z_near;
+        let splits = split_distances(&self.shadows, viewport.z_near, viewport.z_far * SHADOW_DISTANCE);
+        let texels = self.shadows.resolution as f32 / 2.0;
 
-        let mut last_split_dist = 0.0;
-        for (i, split_dist) in settings::SHADOW_CASCADE_SPLITS.iter().enumerate() {
+        let mut last_split = viewport.z_near;
+        for (i, split) in splits.iter().enumerate() {
             #[rustfmt::skip]
             let mut frustum_corners = [
                 vec3(-1.0, 1.0,-1.0),
@@ -122,10 +199,14 @@ impl Environment {
                 let inverted = inv_cam * frustum_corners[i].extend(1.0);
                 frustum_corners[i] = inverted.truncate() / inverted.w;
             }
+
+            // The view depth changes linearly along the edges from the near to the far corners
+            let start = (last_split - viewport.z_near) / (viewport.z_far - viewport.z_near);
+            let end = (split - viewport.z_near) / (viewport.z_far - viewport.z_near);
             for i in 0..4 {
                 let dist = frustum_corners[i + 4] - frustum_corners[i];
-                frustum_corners[i + 4] = frustum_corners[i] + (dist * *split_dist);
-                frustum_corners[i] = frustum_corners[i] + (dist * last_split_dist);
+                frustum_corners[i + 4] = frustum_corners[i] + (dist * end);
+                frustum_corners[i] = frustum_corners[i] + (dist * start);
             }
 
             let mut center = Vector3::zero();
@@ -138,13 +219,42 @@ impl Environment {
             for corner in frustum_corners.iter() {
                 radius = radius.max(corner.distance(center));
             }
+            let interval = self.shadows.update_intervals[i];
+            let light_dir = self.daylight.light_dir;
+            let refresh = self.cascades[i].map_or(true, |cascade| {
+                cascade.age + 1 >= interval
+                    || center.distance(cascade.center) + radius > cascade.radius
+                    || cascade.light_dir.angle(light_dir) > Deg(self.shadows.light_threshold).into()
+            });
+            self.shadow_updated[i] = refresh;
+            self.uniforms.data.shadow_split_depth[i][0] = *split;
+            self.uniforms.data.shadow_split_depth[i][1] = last_split;
+            last_split = *split;
+            if !refresh {
+                self.cascades[i].as_mut().unwrap().age += 1;
+                continue;
+            }
+
+            // Cascades that are kept for a few frames get a margin so the camera can move without leaving them
+            if interval > 1 {
+                radius *= 1.0 + self.shadows.update_margin;
+            }
             radius = (radius * 16.0).ceil() / 16.0;
+            self.cascades[i] = Some(Cascade {
+                center,
+                radius,
+                light_dir,
+                age: 0,
+            });
 
-            let light_dir = settings::LIGHT_DIR;
             let light_view_matrix = Matrix4::look_at_rh(
                 Point3::from_vec(center - light_dir * radius),
                 Point3::from_vec(center),
-                Vector3::unit_y(),
+                if light_dir.y.abs() > 0.99 {
+                    Vector3::unit_z()
+                } else {
+                    Vector3::unit_y()
+                },
             );
 
             let r2 = radius * 2.0;
@@ -154,11 +264,25 @@ impl Environment {
             light_ortho_matrix[2][2] = -1.0 / r2;
             light_ortho_matrix[3][3] = 1.0;
 
+            // The sphere keeps the size of the cascade fixed as the camera turns, moving it in whole texels keeps its edges
+            // from crawling as the camera moves
+            let origin = (light_ortho_matrix * light_view_matrix).w * texels;
+            light_ortho_matrix[3][0] = (origin.x.round() - origin.x) / texels;
+            light_ortho_matrix[3][1] = (origin.y.round() - origin.y) / texels;
+
             let shadow_matrix = light_ortho_matrix * light_view_matrix;
             self.shadow_matrix[i] = shadow_matrix;
             self.uniforms.data.shadow_matrix[i] = shadow_matrix.into();
-            self.uniforms.data.shadow_split_depth[i] = [viewport.z_near + *split_dist * clip_range, 0.0, 0.0, 0.0];
-            last_split_dist = *split_dist;
+            self.uniforms.data.shadow_split_depth[i][2] = r2 / self.shadows.resolution as f32;
+        }
+
+        // Every view picks and morphs the LODs from the main camera so reflections, refractions and shadows match it
+        self.lod_origin = viewport.eye.to_vec();
+        self.lod_z_far = viewport.z_far;
+        self.uniforms.data.lod_origin = self.lod_origin.into();
+        for i in 0..self.lods.len() {
+            let (start, end) = plane::get_morph_range(i, viewport.z_far, self.tile_size, &self.lods);
+            self.uniforms.data.lod_morph[i] = [start, end, 0.0, 0.0];
         }
 
         self.uniforms.data.time = time.elapsed().as_millis() as f32;
@@ -166,3 +290,61 @@ impl Environment {
         queue.write_buffer(&self.uniforms.buffer, 0, bytemuck::cast_slice(&[self.uniforms.data]));
     }
 }
+
+fn split_distances(shadows: &config::ShadowConfig, near: f32, far: f32) -> Vec<f32> {
+    let count = shadows.cascades as usize;
+    (1..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let uniform = near + (far - near) * t;
+            let logarithmic = near * (far / near).powf(t);
+            match shadows.split_scheme {
+                config::SplitScheme::Manual => near + (far - near) * shadows.cascade_splits[i - 1],
+                config::SplitScheme::Uniform => uniform,
+                config::SplitScheme::Logarithmic => logarithmic,
+                config::SplitScheme::Practical(lambda) => logarithmic * lambda + uniform * (1.0 - lambda),
+            }
+        })
+        .collect()
+}
+
+fn create_shadow_maps(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    shadows: &config::ShadowConfig,
+    shadow_sampler: &wgpu::Sampler,
+    depth_sampler: &wgpu::Sampler,
+) -> (Vec<wgpu::TextureView>, wgpu::BindGroup) {
+    let cascade_count = shadows.cascades as usize;
+    let shadow_texture_view: Vec<wgpu::TextureView> = (0..cascade_count)
+        .map(|_| texture::create_view(&device, shadows.resolution, shadows.resolution, settings::DEPTH_TEXTURE_FORMAT))
+        .collect();
+
+    // The binding always holds MAX_SHADOW_CASCADES views, unused slots repeat the last cascade
+    let t: &[&wgpu::TextureView; settings::MAX_SHADOW_CASCADES] = &(0..settings::MAX_SHADOW_CASCADES)
+        .map(|i| &shadow_texture_view[i.min(cascade_count - 1)])
+        .collect::<Vec<_>>()
+        .try_into()
+        .unwrap();
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("deferred_textures"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureViewArray(t),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(shadow_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(depth_sampler),
+            },
+        ],
+    });
+
+    (shadow_texture_view, bind_group)
+}
@@ -8,9 +8,19 @@ pub struct Uniforms {
     pub ambient_strength: f32,
     pub light_color: [f32; 3],
     pub light_intensity: f32,
-    pub shadow_matrix: [[[f32; 4]; 4]; settings::SHADOW_CASCADE_SPLITS.len()],
-    pub shadow_split_depth: [[f32; 4]; settings::SHADOW_CASCADE_SPLITS.len()],
+    pub shadow_matrix: [[[f32; 4]; 4]; settings::MAX_SHADOW_CASCADES],
+    pub shadow_split_depth: [[f32; 4]; settings::MAX_SHADOW_CASCADES],
+    pub lod_morph: [[f32; 4]; settings::MAX_LODS],
+    pub lod_origin: [f32; 3],
+    pub lod_count: u32,
+    pub fog_color: [f32; 3],
     pub time: f32,
+    pub shadow_cascade_count: u32,
+    pub shadow_filter: u32,
+    pub shadow_blend: f32,
+    pub shadow_filter_radius: f32,
+    pub shadow_light_size: f32,
+    pub padding: [f32; 3],
 }
 
 pub struct UniformBuffer {
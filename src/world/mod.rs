@@ -1,14 +1,17 @@
-use crate::{camera, noise, plane, settings};
+use crate::{camera, config, map, noise, plane, settings};
 use cgmath::*;
 use std::{collections::HashMap, time::Instant};
+mod culling;
 mod enivornment;
-mod map;
 mod node;
 mod node_assets;
-mod node_uniforms;
+mod node_builder;
+mod node_cache;
 mod systems;
 mod views;
 
+pub use crate::map::sculpt::Brush;
+
 pub struct WorldData {
     pub terrain: systems::terrain::Terrain,
     pub water: systems::water::Water,
@@ -18,6 +21,10 @@ pub struct WorldData {
     pub environment: enivornment::Environment,
     pub lods: Vec<HashMap<plane::ConnectType, plane::LodBuffer>>,
     pub map: map::Map,
+    pub builder: node_builder::NodeBuilder,
+    pub nodes: node_cache::NodeCache,
+    pub culling: culling::Culling,
+    pub config: config::WorldConfig,
 }
 
 pub struct World {
@@ -28,22 +35,41 @@ pub struct World {
 }
 
 impl World {
-    pub async fn new(device: &wgpu::Device, queue: &wgpu::Queue, viewport: &camera::Viewport) -> Self {
-        let tile = plane::Plane::new(settings::TILE_SIZE);
-        let lods = (0..=settings::LODS.len())
+    pub async fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        viewport: &camera::Viewport,
+        config: config::WorldConfig,
+        timestamp_period: f32,
+    ) -> Self {
+        let tile = plane::Plane::new(config.map.tile_size);
+        let lods = (0..=config.lods.len())
             .map(|lod| tile.create_indices(&device, lod as u32 + 1))
-            .collect();
+            .collect::<Vec<_>>();
 
-        let noise = noise::Noise::new(device, queue).await;
-        let map = map::Map::new(device, queue, &noise).await;
+        let noise = noise::Noise::new(device, queue, &config.map.seed).await;
+        let map = map::Map::new(device, queue, &noise, &config.map, timestamp_period).await;
 
-        let environment = enivornment::Environment::new(device);
-        let water = systems::water::Water::new(device, viewport, &noise, &tile, &environment);
-        let sky = systems::sky::Sky::new(device, viewport);
-        let assets = systems::assets::Assets::new(device, queue, viewport, &noise, &environment);
-        let terrain = systems::terrain::Terrain::new(device, queue, viewport, &noise, &tile, &map, &environment);
+        let environment = enivornment::Environment::new(device, &config);
+        let assets = systems::assets::Assets::new(device, queue, viewport, &noise, &environment, &config.shadows);
+        let culling = culling::Culling::new(device, viewport, &config, &lods, &assets.assets, map.size);
+        let water = systems::water::Water::new(device, viewport, &noise, &tile, &map, &environment, &culling);
+        let sky = systems::sky::Sky::new(device, viewport, &environment.daylight);
+        let terrain = systems::terrain::Terrain::new(
+            device,
+            queue,
+            viewport,
+            &noise,
+            &tile,
+            &map,
+            &environment,
+            &culling,
+            &config.shadows,
+        );
+        let builder = node_builder::NodeBuilder::new(map.heightfield.clone(), assets.assets.clone(), config.map.clone());
+        let nodes = node_cache::NodeCache::new(&config.nodes);
 
-        let mut data = WorldData {
+        let data = WorldData {
             terrain,
             water,
             noise,
@@ -51,11 +77,15 @@ impl World {
             sky,
             lods,
             map,
+            builder,
+            nodes,
+            culling,
             environment,
+            config,
         };
 
-        let root_node = node::Node::new(0.0, 0.0, settings::TILE_DEPTH);
-        let views = views::Views::new(device, &mut data, viewport, &root_node);
+        let root_node = node::Node::new(0.0, 0.0, data.config.map.tile_depth, data.config.map.tile_size);
+        let views = views::Views::new(device, &data, viewport);
 
         Self {
             tile,
@@ -68,19 +98,129 @@ impl World {
     pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, viewport: &camera::Viewport, time: Instant) {
         let view = Matrix4::look_at_rh(viewport.eye, viewport.target, Vector3::unit_y());
 
-        self.root_node.update(device, &mut self.data, viewport);
+        if self.data.map.update(device, queue, viewport.eye) {
+            let center = self.data.map.get_center();
+            let (tile_size, tile_depth) = (self.data.config.map.tile_size, self.data.config.map.tile_depth);
+            let chunk_size = self.data.map.chunk_size as f32;
+            let half_size = self.data.map.size as f32 / 2.0;
+            self.data
+                .nodes
+                .invalidate_outside(center - vec2(half_size, half_size), center + vec2(half_size, half_size));
+
+            let root_node = std::mem::replace(&mut self.root_node, node::Node::new(center.x, center.y, tile_depth, tile_size));
+            let mut chunks = root_node.detach(chunk_size);
+            self.root_node.attach(&mut chunks, chunk_size, tile_size);
+            self.data.culling.changed = true;
+        }
+
+        for leaf in self.data.builder.finished(settings::MAX_LEAVES_PER_FRAME) {
+            if self.root_node.finish(leaf) {
+                self.data.culling.changed = true;
+            }
+        }
+        self.root_node.update(&mut self.data, viewport);
+        self.data.nodes.update(self.root_node.get_stats());
+        if self.data.culling.changed {
+            self.data.culling.upload(device, queue, &self.root_node, &self.data.assets.assets);
+        }
         self.data.environment.update(queue, viewport, view, time);
-        self.views.update(device, queue, &self.data, viewport, &self.root_node, &view);
+        self.data.sky.update(queue, &self.data.environment.daylight);
+        self.views.update(device, queue, &self.data, viewport, &view);
+        // The depth of this frame is reduced into the Hi-Z after it has been rendered, so the next frame can occlude with it
+        self.data.culling.occlusion.ready = true;
+    }
+
+    pub fn sculpt(&mut self, queue: &wgpu::Queue, brush: Brush, center: Vector2<f32>, radius: f32, seconds: f32) {
+        if let Some((min, max)) = self.data.map.sculpt(queue, brush, center, radius, seconds) {
+            self.refresh(min, max);
+        }
+    }
+
+    // Applies a changed map config to a rectangle of the map without regenerating the rest of it
+    pub fn regenerate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        map_config: &config::MapConfig,
+        min: Vector2<f32>,
+        max: Vector2<f32>,
+    ) -> Result<(), String> {
+        let (min, max) = futures::executor::block_on(self.data.map.regenerate(device, queue, map_config, min, max))?;
+        self.data.config.map = map_config.clone();
+        self.refresh(min, max);
+        Ok(())
+    }
+
+    // Only the shadow pipelines are rebuilt, the views are recreated when the bundles they recorded are out of date
+    pub fn set_shadows(&mut self, device: &wgpu::Device, viewport: &camera::Viewport, shadows: &config::ShadowConfig) {
+        let pipelines = shadows.depth_bias != self.data.config.shadows.depth_bias;
+        if pipelines {
+            self.data.terrain.set_shadows(device, shadows);
+            self.data.assets.set_shadows(device, shadows);
+        }
+        let maps = self.data.environment.set_shadows(device, shadows);
+        self.data.config.shadows = shadows.clone();
+
+        if pipelines || maps {
+            self.views = views::Views::new(device, &self.data, viewport);
+            self.data.culling.occlusion.ready = false;
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.data.map.end_stroke();
+    }
+
+    // Returns false when there is nothing to undo
+    pub fn undo(&mut self, queue: &wgpu::Queue) -> bool {
+        match self.data.map.undo(queue) {
+            Some((min, max)) => {
+                self.refresh(min, max);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, queue: &wgpu::Queue) -> bool {
+        match self.data.map.redo(queue) {
+            Some((min, max)) => {
+                self.refresh(min, max);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn refresh(&mut self, min: Vector2<f32>, max: Vector2<f32>) {
+        self.data.nodes.invalidate(min, max);
+        self.root_node.refresh(&mut self.data, min, max);
     }
 
     pub fn resize(&mut self, device: &wgpu::Device, viewport: &camera::Viewport) {
-        self.data.water = systems::water::Water::new(device, viewport, &self.data.noise, &self.tile, &self.data.environment);
-        self.data.sky = systems::sky::Sky::new(device, viewport);
+        self.data.water = systems::water::Water::new(
+            device,
+            viewport,
+            &self.data.noise,
+            &self.tile,
+            &self.data.map,
+            &self.data.environment,
+            &self.data.culling,
+        );
+        self.data.sky = systems::sky::Sky::new(device, viewport, &self.data.environment.daylight);
+        self.data.culling.resize(device, viewport);
         self.views.resize(device, &self.data, viewport);
     }
 
-    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, color_target: &wgpu::TextureView, depth_target: &wgpu::TextureView) {
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        color_target: &wgpu::TextureView,
+        depth_target: &wgpu::TextureView,
+    ) {
         optick::event!();
         self.views.render(encoder, &self.data, color_target, depth_target);
+        self.data.culling.occlusion.build(device, encoder, depth_target);
     }
 }
@@ -1,11 +1,11 @@
-use super::{node_uniforms, systems, WorldData};
-use crate::{camera, settings, world::node_assets};
+use super::{node_builder, node_cache, systems, WorldData};
+use crate::camera;
 use cgmath::*;
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
 pub struct NodeData {
     pub asset_instances: HashMap<String, Vec<systems::assets::Instance>>,
-    pub uniforms: node_uniforms::UniformBuffer,
+    pub water_level: Option<f32>,
 }
 
 enum NodeTree {
@@ -22,11 +22,12 @@ pub struct Node {
     size: f32,
     radius: f32,
     depth: u32,
+    pending: Option<u64>,
 }
 
 impl Node {
-    pub fn new(x: f32, z: f32, depth: u32) -> Self {
-        let size = 2.0f32.powf(depth as f32) * settings::TILE_SIZE as f32;
+    pub fn new(x: f32, z: f32, depth: u32, tile_size: u32) -> Self {
+        let size = 2.0f32.powf(depth as f32) * tile_size as f32;
         let radius = (size * size + size * size).sqrt() / 2.0;
         let bounding_box = camera::BoundingBox {
             min: Point3::new(x - size / 2.0, -10000.0, z - size / 2.0),
@@ -41,34 +42,76 @@ impl Node {
             depth,
             size,
             radius,
+            pending: None,
         }
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, world: &mut WorldData, viewport: &camera::Viewport) {
+    pub fn update(&mut self, world: &mut WorldData, viewport: &camera::Viewport) {
         let distance = self.get_distance(viewport.eye.x, viewport.eye.z);
         let z_far_range = num_traits::Float::sqrt(viewport.z_far.powf(2.0) + viewport.z_far.powf(2.0));
 
-        if distance > z_far_range {
-            self.tree = NodeTree::None;
+        // Nodes are unloaded further away than they are loaded so they don't flap at the boundary
+        if distance > z_far_range * (1.0 + world.config.nodes.hysteresis) {
+            if let NodeTree::None = self.tree {
+                return;
+            }
+
+            let node = mem::replace(self, Node::new(self.x, self.z, self.depth, world.config.map.tile_size));
+            world.nodes.insert(node);
+            world.culling.changed = true;
             return;
         }
 
         match self.tree {
             NodeTree::None => {
-                if self.depth == 0 {
+                if distance > z_far_range {
+                    return;
+                }
+
+                // Nodes of chunks that are not generated yet are left in the cache until their terrain is back
+                let generated = world.map.read().is_generated(vec2(self.x, self.z));
+                if let Some(node) = if generated { world.nodes.take(self.key()) } else { None } {
+                    *self = node;
+                    world.culling.changed = true;
+                    self.update(world, viewport);
+                } else if self.depth == 0 {
                     let distance = vec2(self.x, self.z).distance(vec2(viewport.eye.x, viewport.eye.z)) - self.radius;
-                    if distance < z_far_range {
-                        self.build_leaf_node(device, world);
+                    if distance < z_far_range && world.map.read().is_generated(vec2(self.x, self.z)) {
+                        self.request_leaf(world);
                     }
                 } else {
-                    self.add_children(device, world, viewport);
+                    self.add_children(world, viewport);
                 }
             }
-            _ => {}
+            NodeTree::Branch(ref mut children) => {
+                for child in children.iter_mut() {
+                    child.update(world, viewport);
+                }
+            }
+            NodeTree::Leaf(_) => {}
+        }
+    }
+
+    // Splits the tree into its chunk sized subtrees so they can be moved to a new root
+    pub fn detach(self, chunk_size: f32) -> Vec<Node> {
+        if self.size <= chunk_size {
+            return vec![self];
+        }
+
+        match self.tree {
+            NodeTree::Branch(children) => children.into_iter().flat_map(|child| child.detach(chunk_size)).collect(),
+            _ => vec![],
         }
     }
 
-    pub fn add_children(&mut self, device: &wgpu::Device, world: &mut WorldData, viewport: &camera::Viewport) {
+    pub fn attach(&mut self, nodes: &mut Vec<Node>, chunk_size: f32, tile_size: u32) {
+        if self.size <= chunk_size {
+            if let Some(i) = nodes.iter().position(|n| n.x == self.x && n.z == self.z) {
+                *self = nodes.swap_remove(i);
+            }
+            return;
+        }
+
         let mut children = vec![];
         let child_size = self.size / 2.0;
 
@@ -78,8 +121,9 @@ impl Node {
                     self.x + ((cx as f32 + 0.5) * child_size),
                     self.z + ((cz as f32 + 0.5) * child_size),
                     self.depth - 1,
+                    tile_size,
                 );
-                child.update(device, world, viewport);
+                child.attach(nodes, chunk_size, tile_size);
                 self.bounding_box = self.bounding_box.grow(&child.bounding_box);
                 children.push(child);
             }
@@ -88,46 +132,131 @@ impl Node {
         self.tree = NodeTree::Branch(Box::new(children));
     }
 
-    fn build_leaf_node(&mut self, device: &wgpu::Device, world: &mut WorldData) {
-        let mut asset_instances: HashMap<String, Vec<systems::assets::Instance>> = HashMap::new();
-        let (y_min, y_max) = world.map.min_max_elevation(self.x, self.z, settings::TILE_SIZE);
-        self.bounding_box.min.y = y_min;
-        self.bounding_box.max.y = y_max.max(0.0);
+    // Rebuilds the leaves overlapping an edited area so their bounds and assets match the new heights
+    pub fn refresh(&mut self, world: &mut WorldData, min: Vector2<f32>, max: Vector2<f32>) {
+        if !self.overlaps(min, max) {
+            return;
+        }
 
-        for (name, asset) in &world.assets.assets {
-            let node_assets = node_assets::create_assets(self.x, self.z, self.size, world, &asset, &format!("{}", name.clone()));
-            for node_asset in node_assets {
-                let asset_bb = asset.bounding_box.transform(node_asset.transform);
-                self.bounding_box = self.bounding_box.grow(&asset_bb);
+        match self.tree {
+            NodeTree::Branch(ref mut children) => {
+                for child in children.iter_mut() {
+                    child.refresh(world, min, max);
+                }
+            }
+            NodeTree::Leaf(_) => self.pending = Some(world.builder.request(self.x, self.z, self.size)),
+            NodeTree::None => {}
+        }
+    }
 
-                let instances = asset_instances.entry(name.clone()).or_insert(vec![]);
-                instances.push(node_asset);
+    pub fn add_children(&mut self, world: &mut WorldData, viewport: &camera::Viewport) {
+        let mut children = vec![];
+        let child_size = self.size / 2.0;
+
+        for cz in -1..1 {
+            for cx in -1..1 {
+                let mut child = Node::new(
+                    self.x + ((cx as f32 + 0.5) * child_size),
+                    self.z + ((cz as f32 + 0.5) * child_size),
+                    self.depth - 1,
+                    world.config.map.tile_size,
+                );
+                child.update(world, viewport);
+                self.bounding_box = self.bounding_box.grow(&child.bounding_box);
+                children.push(child);
+            }
+        }
+
+        self.tree = NodeTree::Branch(Box::new(children));
+    }
+
+    // The terrain of a leaf is drawn straight away, its bounds, water and assets follow once a builder has finished them
+    fn request_leaf(&mut self, world: &mut WorldData) {
+        // Water is drawn until the leaf knows whether it has any
+        self.tree = NodeTree::Leaf(NodeData {
+            asset_instances: HashMap::new(),
+            water_level: Some(0.0),
+        });
+        self.pending = Some(world.builder.request(self.x, self.z, self.size));
+        world.culling.changed = true;
+    }
+
+    // Hands a finished leaf to the node waiting for it and refits the bounding boxes on the way, returns false when nothing was waiting
+    pub fn finish(&mut self, leaf: node_builder::Leaf) -> bool {
+        let half_size = self.size / 2.0;
+        if (leaf.x - self.x).abs() > half_size || (leaf.z - self.z).abs() > half_size {
+            return false;
+        }
+
+        match &mut self.tree {
+            NodeTree::Branch(children) => {
+                let finished = match children.iter_mut().find(|c| c.contains(leaf.x, leaf.z)) {
+                    Some(child) => child.finish(leaf),
+                    None => false,
+                };
+                self.bounding_box = children
+                    .iter()
+                    .skip(1)
+                    .fold(children[0].bounding_box.clone(), |bb, child| bb.grow(&child.bounding_box));
+                finished
+            }
+            NodeTree::Leaf(data) if self.pending == Some(leaf.version) => {
+                data.asset_instances = leaf.asset_instances;
+                data.water_level = leaf.water_level;
+                self.bounding_box = leaf.bounding_box;
+                self.pending = None;
+                true
             }
+            _ => false,
         }
+    }
+
+    pub fn overlaps(&self, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+        let half_size = self.size / 2.0;
+        self.x + half_size >= min.x && self.x - half_size <= max.x && self.z + half_size >= min.y && self.z - half_size <= max.y
+    }
+
+    pub fn is_inside(&self, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+        let half_size = self.size / 2.0;
+        self.x - half_size >= min.x && self.x + half_size <= max.x && self.z - half_size >= min.y && self.z + half_size <= max.y
+    }
 
-        let size = (settings::TILE_SIZE * 2u32.pow(settings::TILE_DEPTH)) as f32;
-        let uniforms = node_uniforms::UniformBuffer::new(
-            device,
-            &world.terrain.node_uniform_bind_group_layout,
-            node_uniforms::Uniforms {
-                translation: [self.x, self.z],
-                size,
-            },
-        );
+    pub fn key(&self) -> (i32, i32, u32) {
+        (self.x.round() as i32, self.z.round() as i32, self.depth)
+    }
 
-        self.tree = NodeTree::Leaf(NodeData { asset_instances, uniforms });
+    pub fn is_settled(&self) -> bool {
+        match &self.tree {
+            NodeTree::Branch(children) => children.iter().all(|child| child.is_settled()),
+            _ => self.pending.is_none(),
+        }
     }
 
-    pub fn get_nodes<'a>(&'a self, frustum: &camera::FrustumCuller) -> Vec<&'a Self> {
-        optick::event!();
-        if frustum.test_bounding_box(&self.bounding_box) {
-            match &self.tree {
-                NodeTree::Branch(children) => children.iter().flat_map(|child| child.get_nodes(frustum)).collect(),
-                NodeTree::Leaf(_) => vec![&self],
-                NodeTree::None => vec![],
+    pub fn get_stats(&self) -> node_cache::NodeStats {
+        let mut stats = node_cache::NodeStats::default();
+        match &self.tree {
+            NodeTree::Branch(children) => children.iter().for_each(|child| stats.add(child.get_stats())),
+            NodeTree::Leaf(data) => {
+                let instances = data.asset_instances.values().map(|i| i.len()).sum();
+                stats.leaves = 1;
+                stats.instances = instances;
+                stats.bytes = mem::size_of::<Node>() + instances * mem::size_of::<systems::assets::Instance>();
             }
-        } else {
-            vec![]
+            NodeTree::None => {}
+        }
+        stats
+    }
+
+    fn contains(&self, x: f32, z: f32) -> bool {
+        let half_size = self.size / 2.0;
+        (x - self.x).abs() < half_size && (z - self.z).abs() < half_size
+    }
+
+    pub fn get_leaves<'a>(&'a self) -> Vec<&'a Self> {
+        match &self.tree {
+            NodeTree::Branch(children) => children.iter().flat_map(|child| child.get_leaves()).collect(),
+            NodeTree::Leaf(_) => vec![&self],
+            NodeTree::None => vec![],
         }
     }
 
@@ -1,7 +1,7 @@
 use std::f32::consts::PI;
 
-use super::{systems, WorldData};
-use crate::settings;
+use super::systems;
+use crate::{config, map::Heightfield};
 use cgmath::*;
 use rand::Rng;
 use rand_pcg::Pcg64;
@@ -14,14 +14,16 @@ struct NodeAsset {
     scale: f32,
     temp_probability: f32,
     moist_probability: f32,
+    biome_probability: f32,
     key: String,
 }
 
 impl NodeAsset {
-    fn new(rng: &mut Pcg64, x: f32, z: f32, size: f32, world: &WorldData, mesh: &systems::assets::Asset, key: String) -> Self {
+    fn new(rng: &mut Pcg64, x: f32, z: f32, size: f32, map: &Heightfield, mesh: &systems::assets::Asset, key: String) -> Option<Self> {
         let m = vec2(x + (rng.gen::<f32>() - 0.5) * size, z + (rng.gen::<f32>() - 0.5) * size);
         let scale = rng.gen_range(mesh.size_range[0]..mesh.size_range[1]);
-        let (temp, moist) = world.map.get_biome(m);
+        let biome = map.get_biome(m)?;
+        let (temp, moist) = (biome.temperature, biome.moisture);
 
         let temp_probability = if temp < mesh.temp_preferred {
             (mesh.temp_preferred - mesh.temp_range[0]).abs() / 1.0 - (mesh.temp_preferred - temp).abs()
@@ -35,26 +37,38 @@ impl NodeAsset {
             (mesh.moist_preferred - mesh.moist_range[1]).abs() / 1.0 - (mesh.moist_preferred - moist).abs()
         };
 
-        let (pos, _) = world.map.get_position_normal(m);
+        // Assets restricted to named biomes are placed in proportion to how much of those biomes is blended in here
+        let biome_probability = match mesh.biomes.is_empty() {
+            true => 1.0,
+            false => biome
+                .weights
+                .iter()
+                .filter(|(b, _)| mesh.biomes.iter().any(|name| name == map.biomes.get_name(*b)))
+                .map(|(_, weight)| weight)
+                .sum(),
+        };
+
+        let (pos, _) = map.get_position_normal(m)?;
         let normal = get_offsets(mesh.radius, scale)
             .iter()
-            .map(|o| world.map.get_smooth_normal(vec2(pos.x + o.x, pos.z + o.z)))
-            .sum::<Vector3<f32>>()
+            .map(|o| map.get_normal(vec2(m.x + o.x, m.y + o.z)))
+            .sum::<Option<Vector3<f32>>>()?
             / 4.0;
 
-        Self {
+        Some(Self {
             pos: m,
             tile: pos,
             normal,
             scale,
             temp_probability,
             moist_probability,
+            biome_probability,
             key,
-        }
+        })
     }
 
-    fn validate(&self, mesh: &systems::assets::Asset) -> bool {
-        let seed = format!("{}_CREATE_{}_{}_{}", settings::MAP_SEED, self.tile.x, self.tile.z, self.key);
+    fn validate(&self, mesh: &systems::assets::Asset, map: &Heightfield, map_config: &config::MapConfig) -> bool {
+        let seed = format!("{}_CREATE_{}_{}_{}", map_config.seed, self.tile.x, self.tile.z, self.key);
         let mut rng: Pcg64 = Seeder::from(seed).make_rng();
 
         if 1.0 - self.normal.y < mesh.slope_range[0] || 1.0 - self.normal.y > mesh.slope_range[1] {
@@ -65,24 +79,30 @@ impl NodeAsset {
             return false;
         }
 
-        if self.tile.y <= 0.0 {
+        if self.tile.y <= 0.0 || map.get_water(vec2(self.tile.x, self.tile.z)).is_some() {
+            return false;
+        }
+
+        if self.biome_probability < rng.gen() {
             return false;
         }
 
         true
     }
 
-    fn create_instance(&self, mesh: &systems::assets::Asset, world: &WorldData) -> systems::assets::Instance {
-        let seed = format!("{}_TRANSFORM_{}_{}_{}", settings::MAP_SEED, self.tile.x, self.tile.z, self.key);
+    fn create_instance(
+        &self,
+        mesh: &systems::assets::Asset,
+        map: &Heightfield,
+        map_config: &config::MapConfig,
+    ) -> Option<systems::assets::Instance> {
+        let seed = format!("{}_TRANSFORM_{}_{}_{}", map_config.seed, self.tile.x, self.tile.z, self.key);
         let mut rng: Pcg64 = Seeder::from(seed).make_rng();
 
         let elev = get_offsets(mesh.radius, self.scale)
             .iter()
-            .map(|o| {
-                let p = vec3(self.tile.x + o.x, self.tile.y, self.tile.z + o.z);
-                world.map.get_smooth_elevation(self.pos, (p, self.normal))
-            })
-            .sum::<f32>()
+            .map(|o| map.get_elevation(vec2(self.pos.x + o.x, self.pos.y + o.z)))
+            .sum::<Option<f32>>()?
             / 4.0;
 
         let mut r = Quaternion::from(Euler {
@@ -96,7 +116,7 @@ impl NodeAsset {
         }
 
         let t = Matrix4::from_translation(vec3(self.pos.x, elev, self.pos.y)) * Matrix4::from(r) * Matrix4::from_scale(self.scale);
-        systems::assets::Instance { transform: t.into() }
+        Some(systems::assets::Instance { transform: t.into() })
     }
 }
 
@@ -122,21 +142,22 @@ pub fn create_assets(
     x: f32,
     z: f32,
     size: f32,
-    world: &WorldData,
+    map: &Heightfield,
+    map_config: &config::MapConfig,
     mesh: &systems::assets::Asset,
     key: &str,
 ) -> Vec<systems::assets::Instance> {
-    let seed = format!("{}_NODE_{}_{}_{}", settings::MAP_SEED, x, z, key);
+    let seed = format!("{}_NODE_{}_{}_{}", map_config.seed, x, z, key);
     let mut rng: Pcg64 = Seeder::from(seed).make_rng();
-    let mut count = (rng.gen::<f32>() * mesh.density.floor() * settings::TILE_SIZE as f32 / 60.0) as usize;
+    let mut count = (rng.gen::<f32>() * mesh.density.floor() * map_config.tile_size as f32 / 60.0) as usize;
 
     if rng.gen::<f32>() < mesh.density.fract() {
         count += 1;
     }
 
     (0..count)
-        .map(|_| NodeAsset::new(&mut rng, x, z, size, world, mesh, key.to_string()))
-        .filter(|node_asset| node_asset.validate(&mesh))
-        .map(|node_asset| node_asset.create_instance(mesh, world))
+        .filter_map(|_| NodeAsset::new(&mut rng, x, z, size, map, mesh, key.to_string()))
+        .filter(|node_asset| node_asset.validate(&mesh, map, map_config))
+        .filter_map(|node_asset| node_asset.create_instance(mesh, map, map_config))
         .collect()
 }
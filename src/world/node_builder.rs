@@ -0,0 +1,149 @@
+use super::{node_assets, systems};
+use crate::{camera, config, map::Heightfield, settings};
+use cgmath::*;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    thread,
+};
+
+struct Request {
+    x: f32,
+    z: f32,
+    size: f32,
+    version: u64,
+}
+
+pub struct Leaf {
+    pub x: f32,
+    pub z: f32,
+    pub version: u64,
+    pub bounding_box: camera::BoundingBox,
+    pub water_level: Option<f32>,
+    pub asset_instances: HashMap<String, Vec<systems::assets::Instance>>,
+}
+
+// Builds the CPU side of quadtree leaves on worker threads, the results are picked up by the main thread a few at a time
+pub struct NodeBuilder {
+    requests: Sender<Request>,
+    results: Receiver<Leaf>,
+    latest: Arc<Mutex<HashMap<(i32, i32), u64>>>,
+    version: u64,
+}
+
+impl NodeBuilder {
+    pub fn new(heightfield: Arc<RwLock<Heightfield>>, assets: Arc<systems::assets::AssetMap>, map_config: config::MapConfig) -> Self {
+        let (requests, request_receiver) = unbounded::<Request>();
+        let (result_sender, results) = unbounded();
+        let latest = Arc::new(Mutex::new(HashMap::new()));
+
+        for i in 0..settings::LEAF_WORKERS {
+            let (requests, results) = (request_receiver.clone(), result_sender.clone());
+            let (heightfield, assets, map_config, latest) = (heightfield.clone(), assets.clone(), map_config.clone(), latest.clone());
+
+            thread::Builder::new()
+                .name(format!("leaf_builder_{}", i))
+                .spawn(move || {
+                    for request in requests.iter() {
+                        // Leaves that were requested again while waiting are only built for the latest request
+                        if latest.lock().unwrap().get(&key(request.x, request.z)) != Some(&request.version) {
+                            continue;
+                        }
+
+                        let window = copy_window(&request, &heightfield.read().unwrap(), &assets, &map_config);
+                        let leaf = build_leaf(&request, &window, &assets, &map_config);
+                        if results.send(leaf).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("Failed to start leaf builder");
+        }
+
+        Self {
+            requests,
+            results,
+            latest,
+            version: 0,
+        }
+    }
+
+    // Returns the version the finished leaf will carry
+    pub fn request(&mut self, x: f32, z: f32, size: f32) -> u64 {
+        self.version += 1;
+        self.latest.lock().unwrap().insert(key(x, z), self.version);
+        self.requests
+            .send(Request {
+                x,
+                z,
+                size,
+                version: self.version,
+            })
+            .expect("Leaf builders have stopped");
+        self.version
+    }
+
+    pub fn finished(&self, budget: usize) -> Vec<Leaf> {
+        let leaves: Vec<Leaf> = self.results.try_iter().take(budget).collect();
+
+        // Positions are only tracked while their latest request is outstanding, so streaming does not grow the map
+        let mut latest = self.latest.lock().unwrap();
+        for leaf in leaves.iter() {
+            let key = key(leaf.x, leaf.z);
+            if latest.get(&key) == Some(&leaf.version) {
+                latest.remove(&key);
+            }
+        }
+        leaves
+    }
+}
+
+fn key(x: f32, z: f32) -> (i32, i32) {
+    (x.round() as i32, z.round() as i32)
+}
+
+// Everything a leaf samples, assets can be placed anywhere in the node and sample their radius around them
+fn copy_window(request: &Request, map: &Heightfield, assets: &systems::assets::AssetMap, map_config: &config::MapConfig) -> Heightfield {
+    let reach = assets.values().map(|asset| asset.radius * asset.size_range[1]).fold(0.0, f32::max);
+    let half_size = request.size.max(map_config.tile_size as f32) / 2.0 + reach + 1.0;
+    map.copy_window(
+        vec2(request.x - half_size, request.z - half_size),
+        vec2(request.x + half_size, request.z + half_size),
+    )
+}
+
+fn build_leaf(request: &Request, map: &Heightfield, assets: &systems::assets::AssetMap, map_config: &config::MapConfig) -> Leaf {
+    let (y_min, y_max) = map.min_max_elevation(request.x, request.z, map_config.tile_size);
+    let water_level = map.min_water_level(request.x, request.z, map_config.tile_size);
+    let half_size = request.size / 2.0;
+    let mut bounding_box = camera::BoundingBox {
+        min: Point3::new(
+            request.x - half_size,
+            y_min.min(water_level.unwrap_or(y_min)),
+            request.z - half_size,
+        ),
+        max: Point3::new(request.x + half_size, y_max.max(0.0), request.z + half_size),
+    };
+
+    let mut asset_instances: HashMap<String, Vec<systems::assets::Instance>> = HashMap::new();
+    for (name, asset) in assets.iter() {
+        let node_assets = node_assets::create_assets(request.x, request.z, request.size, map, map_config, &asset, name);
+        for node_asset in node_assets {
+            let asset_bb = asset.bounding_box.transform(node_asset.transform);
+            bounding_box = bounding_box.grow(&asset_bb);
+
+            let instances = asset_instances.entry(name.clone()).or_insert(vec![]);
+            instances.push(node_asset);
+        }
+    }
+
+    Leaf {
+        x: request.x,
+        z: request.z,
+        version: request.version,
+        bounding_box,
+        water_level,
+        asset_instances,
+    }
+}
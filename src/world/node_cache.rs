@@ -0,0 +1,129 @@
+use super::node::Node;
+use crate::{config, logger};
+use cgmath::*;
+use std::collections::HashMap;
+
+#[derive(Default, Copy, Clone)]
+pub struct NodeStats {
+    pub leaves: usize,
+    pub instances: usize,
+    pub bytes: usize,
+}
+
+impl NodeStats {
+    pub fn add(&mut self, other: NodeStats) {
+        self.leaves += other.leaves;
+        self.instances += other.instances;
+        self.bytes += other.bytes;
+    }
+}
+
+struct CachedNode {
+    node: Node,
+    stats: NodeStats,
+    unloaded: u64,
+}
+
+// Keeps unloaded subtrees around so they can be put back without rebuilding them,
+// the ones unloaded the longest ago are evicted first when the nodes exceed the memory budget
+pub struct NodeCache {
+    nodes: HashMap<(i32, i32, u32), CachedNode>,
+    budget: usize,
+    frame: u64,
+    pub live: NodeStats,
+    pub cached: NodeStats,
+}
+
+impl NodeCache {
+    pub fn new(config: &config::NodeConfig) -> Self {
+        Self {
+            nodes: HashMap::new(),
+            budget: config.memory_budget as usize * 1024 * 1024,
+            frame: 0,
+            live: NodeStats::default(),
+            cached: NodeStats::default(),
+        }
+    }
+
+    pub fn insert(&mut self, node: Node) {
+        // Leaves that are still being built would never receive their data once put back
+        if !node.is_settled() {
+            return;
+        }
+
+        let stats = node.get_stats();
+        if stats.leaves == 0 {
+            return;
+        }
+
+        self.cached.add(stats);
+        if let Some(replaced) = self.nodes.insert(
+            node.key(),
+            CachedNode {
+                node,
+                stats,
+                unloaded: self.frame,
+            },
+        ) {
+            self.forget(replaced.stats);
+        }
+    }
+
+    pub fn take(&mut self, key: (i32, i32, u32)) -> Option<Node> {
+        let cached = self.nodes.remove(&key)?;
+        self.forget(cached.stats);
+        Some(cached.node)
+    }
+
+    // Cached nodes overlapping an edited area would put back stale bounds and assets
+    pub fn invalidate(&mut self, min: Vector2<f32>, max: Vector2<f32>) {
+        let stale: Vec<(i32, i32, u32)> = self
+            .nodes
+            .iter()
+            .filter(|(_, c)| c.node.overlaps(min, max))
+            .map(|(k, _)| *k)
+            .collect();
+        for key in stale {
+            self.take(key);
+        }
+    }
+
+    // Cached nodes reaching outside the map window were built from chunks that have been dropped
+    pub fn invalidate_outside(&mut self, min: Vector2<f32>, max: Vector2<f32>) {
+        let stale: Vec<(i32, i32, u32)> = self
+            .nodes
+            .iter()
+            .filter(|(_, c)| !c.node.is_inside(min, max))
+            .map(|(k, _)| *k)
+            .collect();
+        for key in stale {
+            self.take(key);
+        }
+    }
+
+    pub fn update(&mut self, live: NodeStats) {
+        self.frame += 1;
+        self.live = live;
+
+        while self.live.bytes + self.cached.bytes > self.budget {
+            match self.nodes.iter().min_by_key(|(_, c)| c.unloaded).map(|(k, _)| *k) {
+                Some(key) => {
+                    self.take(key);
+                }
+                None => break,
+            }
+        }
+
+        logger::count("Nodes: live leaves", self.live.leaves as u64);
+        logger::count("Nodes: live instances", self.live.instances as u64);
+        logger::count("Nodes: live bytes", self.live.bytes as u64);
+        logger::count("Nodes: cached leaves", self.cached.leaves as u64);
+        logger::count("Nodes: cached bytes", self.cached.bytes as u64);
+    }
+
+    fn forget(&mut self, stats: NodeStats) {
+        self.cached.leaves -= stats.leaves;
+        self.cached.instances -= stats.instances;
+        self.cached.bytes -= stats.bytes;
+    }
+}
@@ -25,6 +25,7 @@ pub struct Asset {
     pub rotation: [(f32, f32); 3],
     pub align: bool,
     pub radius: f32,
+    pub biomes: Vec<String>,
 }
 
 pub type AssetMap = HashMap<String, Asset>;
@@ -77,6 +78,7 @@ pub fn create(
                     moist_preferred: mesh.get_extra_f32("moist_preferred"),
                     radius: mesh.get_extra_f32("radius"),
                     align: mesh.get_extra_bool("align"),
+                    biomes: mesh.get_extra_flags("biome_"),
                     render_distance,
                     rotation,
                     primitives,
@@ -1,30 +1,18 @@
-use crate::{
-    camera, noise, settings, texture,
-    world::{self, node::Node},
-};
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
-use std::{collections::HashMap, time::Instant};
-use wgpu::util::DeviceExt;
+use crate::{camera, config, noise, settings, texture, world};
+use std::{sync::Arc, time::Instant};
 mod assets;
 mod data;
 mod uniforms;
 pub use {self::assets::Asset, self::assets::AssetMap, self::data::Instance, self::data::Vertex};
 
-pub struct InstanceBuffer {
-    pub buffer: wgpu::Buffer,
-    pub render_distance: f32,
-    pub length: u32,
-}
-
-pub type InstanceBufferMap = HashMap<String, InstanceBuffer>;
-
 pub struct Assets {
     pub sampler: wgpu::Sampler,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub shadow_pipeline: wgpu::RenderPipeline,
+    pub shadow_pipelines: Vec<wgpu::RenderPipeline>,
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
-    pub assets: assets::AssetMap,
+    pub assets: Arc<assets::AssetMap>,
     noise_bindings: noise::NoiseBindings,
+    pipeline_layout: wgpu::PipelineLayout,
 }
 
 impl Assets {
@@ -34,6 +22,7 @@ impl Assets {
         viewport: &camera::Viewport,
         noise: &noise::Noise,
         env: &world::enivornment::Environment,
+        shadows: &config::ShadowConfig,
     ) -> Self {
         let noise_bindings = noise.create_bindings(device);
 
@@ -127,41 +116,7 @@ impl Assets {
             multisample: wgpu::MultisampleState::default(),
         });
 
-        let vs_module = device.create_shader_module(&wgpu::include_spirv!("../../../shaders/compiled/assets-shadows.vert.spv"));
-        let fs_module = device.create_shader_module(&wgpu::include_spirv!("../../../shaders/compiled/assets-shadows.frag.spv"));
-        let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("asset_shadow_pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &vs_module,
-                entry_point: "main",
-                buffers: &[data::Vertex::desc(), data::Instance::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fs_module,
-                entry_point: "main",
-                targets: &[],
-            }),
-            primitive: wgpu::PrimitiveState {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: settings::DEPTH_TEXTURE_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState {
-                    constant: 2,
-                    slope_scale: 2.0,
-                    clamp: 0.0,
-                },
-                clamp_depth: true,
-            }),
-            multisample: wgpu::MultisampleState::default(),
-        });
+        let shadow_pipelines = create_shadow_pipelines(device, &pipeline_layout, shadows);
 
         let now = Instant::now();
         let assets = assets::create(device, queue, &uniform_bind_group_layout, &texture_bind_group_layout, &sampler);
@@ -170,24 +125,26 @@ impl Assets {
         Self {
             sampler,
             render_pipeline,
-            shadow_pipeline,
+            shadow_pipelines,
             texture_bind_group_layout,
             noise_bindings,
-            assets,
+            assets: Arc::new(assets),
+            pipeline_layout,
         }
     }
 
+    pub fn set_shadows(&mut self, device: &wgpu::Device, shadows: &config::ShadowConfig) {
+        self.shadow_pipelines = create_shadow_pipelines(device, &self.pipeline_layout, shadows);
+    }
+
     pub fn get_bundle(
         &self,
         device: &wgpu::Device,
-        viewport: &camera::Viewport,
         camera: &camera::Instance,
         world_data: &world::WorldData,
-        asset_instances: &mut InstanceBufferMap,
-        nodes: &Vec<&Node>,
+        view: &world::culling::ViewCulling,
     ) -> wgpu::RenderBundle {
         optick::event!();
-        update_instance_buffer(device, viewport, asset_instances, nodes);
 
         let mut encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
             label: None,
@@ -202,16 +159,16 @@ impl Assets {
         encoder.set_bind_group(4, &world_data.environment.uniforms.bind_group, &[]);
         encoder.set_bind_group(5, &world_data.environment.texture_bind_group, &[]);
 
-        for (key, asset_instances) in asset_instances.iter_mut().filter(|(_, val)| val.length > 0) {
-            let asset = self.assets.get(key).unwrap();
-            encoder.set_vertex_buffer(1, asset_instances.buffer.slice(..));
+        for draw in world_data.culling.asset_draws() {
+            let asset = self.assets.get(draw.name).unwrap();
+            encoder.set_vertex_buffer(1, view.asset_outputs[draw.index].0.slice(..));
 
-            for mesh in &asset.primitives {
+            for (mesh, offset) in asset.primitives.iter().zip(draw.draw_offsets) {
                 encoder.set_bind_group(0, &mesh.uniforms.bind_group, &[]);
                 encoder.set_bind_group(1, &mesh.texture_bind_group, &[]);
                 encoder.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                 encoder.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                encoder.draw_indexed(0..mesh.num_elements, 0, 0..asset_instances.length as _);
+                encoder.draw_indexed_indirect(&view.asset_draws, offset);
             }
         }
 
@@ -221,14 +178,12 @@ impl Assets {
     pub fn get_shadow_bundle(
         &self,
         device: &wgpu::Device,
-        viewport: &camera::Viewport,
         camera: &camera::Instance,
         world_data: &world::WorldData,
-        asset_instances: &mut InstanceBufferMap,
-        nodes: &Vec<&Node>,
+        view: &world::culling::ViewCulling,
+        cascade: usize,
     ) -> wgpu::RenderBundle {
         optick::event!();
-        update_instance_buffer(device, viewport, asset_instances, nodes);
 
         let mut encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
             label: None,
@@ -237,21 +192,21 @@ impl Assets {
             sample_count: 1,
         });
 
-        encoder.set_pipeline(&self.shadow_pipeline);
+        encoder.set_pipeline(&self.shadow_pipelines[cascade]);
         encoder.set_bind_group(2, &camera.uniforms.bind_group, &[]);
         encoder.set_bind_group(3, &self.noise_bindings.bind_group, &[]);
         encoder.set_bind_group(4, &world_data.environment.uniforms.bind_group, &[]);
 
-        for (key, asset_instances) in asset_instances.iter_mut().filter(|(_, val)| val.length > 0) {
-            let asset = self.assets.get(key).unwrap();
-            encoder.set_vertex_buffer(1, asset_instances.buffer.slice(..));
+        for draw in world_data.culling.asset_draws() {
+            let asset = self.assets.get(draw.name).unwrap();
+            encoder.set_vertex_buffer(1, view.asset_outputs[draw.index].0.slice(..));
 
-            for mesh in &asset.primitives {
+            for (mesh, offset) in asset.primitives.iter().zip(draw.draw_offsets) {
                 encoder.set_bind_group(0, &mesh.uniforms.bind_group, &[]);
                 encoder.set_bind_group(1, &mesh.texture_bind_group, &[]);
                 encoder.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                 encoder.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                encoder.draw_indexed(0..mesh.num_elements, 0, 0..asset_instances.length as _);
+                encoder.draw_indexed_indirect(&view.asset_draws, offset);
             }
         }
 
@@ -259,48 +214,52 @@ impl Assets {
             label: Some("assets_shadow"),
         })
     }
-
-    pub fn get_instances(&self, device: &wgpu::Device) -> InstanceBufferMap {
-        let mut asset_instances = HashMap::new();
-
-        for (key, asset) in self.assets.iter() {
-            let instances: Vec<Instance> = vec![];
-            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("instance_buffer"),
-                contents: bytemuck::cast_slice(&instances),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-            asset_instances.insert(
-                key.clone(),
-                InstanceBuffer {
-                    buffer,
-                    render_distance: asset.render_distance,
-                    length: 0,
-                },
-            );
-        }
-
-        asset_instances
-    }
 }
 
-fn update_instance_buffer(device: &wgpu::Device, viewport: &camera::Viewport, asset_instances: &mut InstanceBufferMap, nodes: &Vec<&Node>) {
-    asset_instances.par_iter_mut().for_each(|(key, instance)| {
-        let instances = nodes
-            .iter()
-            .filter(|node| node.get_distance(viewport.eye.x, viewport.eye.z) < instance.render_distance * viewport.z_far)
-            .filter_map(|node| node.get_data())
-            .filter_map(|node_data| node_data.asset_instances.get(key))
-            .flat_map(|instances| instances.clone())
-            .collect::<Vec<Instance>>();
-
-        instance.length = instances.len() as u32;
-        if instance.length > 0 {
-            instance.buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("instance_buffer"),
-                contents: bytemuck::cast_slice(&instances.as_slice()),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        }
-    });
+fn create_shadow_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shadows: &config::ShadowConfig,
+) -> Vec<wgpu::RenderPipeline> {
+    let vs_module = device.create_shader_module(&wgpu::include_spirv!("../../../shaders/compiled/assets-shadows.vert.spv"));
+    let fs_module = device.create_shader_module(&wgpu::include_spirv!("../../../shaders/compiled/assets-shadows.frag.spv"));
+    shadows
+        .depth_bias
+        .iter()
+        .map(|bias| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("asset_shadow_pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[data::Vertex::desc(), data::Instance::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: wgpu::CullMode::Back,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: settings::DEPTH_TEXTURE_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: bias.constant,
+                        slope_scale: bias.slope_scale,
+                        clamp: 0.0,
+                    },
+                    clamp_depth: true,
+                }),
+                multisample: wgpu::MultisampleState::default(),
+            })
+        })
+        .collect()
 }
@@ -1,4 +1,4 @@
-use crate::{camera, settings};
+use crate::{camera, settings, world::enivornment};
 mod uniforms;
 
 pub struct Sky {
@@ -7,7 +7,7 @@ pub struct Sky {
 }
 
 impl Sky {
-    pub fn new(device: &wgpu::Device, viewport: &camera::Viewport) -> Self {
+    pub fn new(device: &wgpu::Device, viewport: &camera::Viewport, daylight: &enivornment::Daylight) -> Self {
         let uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("uniform_bind_group_layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
@@ -53,19 +53,16 @@ impl Sky {
             multisample: wgpu::MultisampleState::default(),
         });
 
-        let uniforms = uniforms::UniformBuffer::new(
-            &device,
-            &uniform_bind_group_layout,
-            uniforms::Uniforms {
-                light_dir: settings::LIGHT_DIR.into(),
-                not_used: 0.0,
-                sky_color: settings::SKY_COLOR.into(),
-            },
-        );
+        let uniforms = uniforms::UniformBuffer::new(&device, &uniform_bind_group_layout, sky_uniforms(daylight));
 
         Self { render_pipeline, uniforms }
     }
 
+    pub fn update(&mut self, queue: &wgpu::Queue, daylight: &enivornment::Daylight) {
+        self.uniforms.data = sky_uniforms(daylight);
+        queue.write_buffer(&self.uniforms.buffer, 0, bytemuck::cast_slice(&[self.uniforms.data]));
+    }
+
     pub fn get_bundle(&self, device: &wgpu::Device, camera: &camera::Instance) -> wgpu::RenderBundle {
         optick::event!();
         let mut encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
@@ -83,3 +80,14 @@ impl Sky {
         encoder.finish(&wgpu::RenderBundleDescriptor { label: Some("sky") })
     }
 }
+
+fn sky_uniforms(daylight: &enivornment::Daylight) -> uniforms::Uniforms {
+    uniforms::Uniforms {
+        sun_dir: daylight.sun_dir.into(),
+        sun_visibility: daylight.sun_visibility,
+        sky_color: daylight.sky_color.into(),
+        not_used: 0.0,
+        moon_dir: daylight.moon_dir.into(),
+        moon_visibility: daylight.moon_visibility,
+    }
+}
@@ -3,9 +3,12 @@ use wgpu::util::DeviceExt;
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniforms {
-    pub light_dir: [f32; 3],
-    pub not_used: f32,
+    pub sun_dir: [f32; 3],
+    pub sun_visibility: f32,
     pub sky_color: [f32; 3],
+    pub not_used: f32,
+    pub moon_dir: [f32; 3],
+    pub moon_visibility: f32,
 }
 
 pub struct UniformBuffer {
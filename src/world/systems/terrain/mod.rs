@@ -1,8 +1,7 @@
 use crate::{
-    camera, noise, plane, settings, texture,
-    world::{self, node::Node},
+    camera, config, map, noise, plane, settings, texture,
+    world::{self, culling},
 };
-use cgmath::*;
 use image::GenericImageView;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::convert::TryInto;
@@ -10,9 +9,10 @@ use wgpu::util::DeviceExt;
 
 pub struct Terrain {
     pub render_pipeline: wgpu::RenderPipeline,
+    pub shadow_pipelines: Vec<wgpu::RenderPipeline>,
     pub texture_bind_group: wgpu::BindGroup,
     pub vertex_buffer: wgpu::Buffer,
-    pub node_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
     noise_bindings: noise::NoiseBindings,
 }
 
@@ -23,8 +23,10 @@ impl Terrain {
         viewport: &camera::Viewport,
         noise: &noise::Noise,
         tile: &plane::Plane,
-        map: &world::map::Map,
+        map: &map::Map,
         lights: &world::enivornment::Environment,
+        culling: &culling::Culling,
+        shadows: &config::ShadowConfig,
     ) -> Terrain {
         let noise_bindings = noise.create_bindings(device);
 
@@ -41,21 +43,17 @@ impl Terrain {
                     },
                     count: None,
                 },
-            ],
-        });
-
-        let node_uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("uniform_bind_group_layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+            ],
         });
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -64,7 +62,7 @@ impl Terrain {
                 &viewport.bind_group_layout,
                 &texture_bind_group_layout,
                 &noise_bindings.bind_group_layout,
-                &node_uniform_bind_group_layout,
+                &culling.render_bind_group_layout,
                 &lights.uniform_bind_group_layout,
                 &lights.texture_bind_group_layout,
                 &map.textures.bind_group_layout,
@@ -104,7 +102,14 @@ impl Terrain {
             multisample: wgpu::MultisampleState::default(),
         });
 
-        let texture_bind_group = build_textures(device, queue, &texture_bind_group_layout);
+        let shadow_pipelines = create_shadow_pipelines(device, &render_pipeline_layout, shadows);
+
+        let biome_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("biome_buffer"),
+            contents: bytemuck::cast_slice(&[map.read().biomes.get_uniforms()]),
+            usage: wgpu::BufferUsage::UNIFORM,
+        });
+        let texture_bind_group = build_textures(device, queue, &texture_bind_group_layout, &biome_buffer);
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("terrain_vertex_buffer"),
@@ -115,18 +120,23 @@ impl Terrain {
         Terrain {
             texture_bind_group,
             render_pipeline,
+            shadow_pipelines,
             noise_bindings,
             vertex_buffer,
-            node_uniform_bind_group_layout,
+            pipeline_layout: render_pipeline_layout,
         }
     }
 
+    pub fn set_shadows(&mut self, device: &wgpu::Device, shadows: &config::ShadowConfig) {
+        self.shadow_pipelines = create_shadow_pipelines(device, &self.pipeline_layout, shadows);
+    }
+
     pub fn get_bundle(
         &self,
         device: &wgpu::Device,
         camera: &camera::Instance,
         world_data: &world::WorldData,
-        nodes: &Vec<&Node>,
+        view: &culling::ViewCulling,
     ) -> wgpu::RenderBundle {
         optick::event!();
         let mut encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
@@ -142,41 +152,100 @@ impl Terrain {
         encoder.set_bind_group(4, &world_data.environment.uniforms.bind_group, &[]);
         encoder.set_bind_group(5, &world_data.environment.texture_bind_group, &[]);
         encoder.set_bind_group(6, &world_data.map.textures.bind_group, &[]);
-        encoder.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-        let direction = camera.uniforms.data.clip[1];
-        let plane = camera.uniforms.data.clip[3];
-        let eye = vec3(
-            camera.uniforms.data.eye_pos[0],
-            camera.uniforms.data.eye_pos[1],
-            camera.uniforms.data.eye_pos[2],
-        );
-
-        for node in nodes {
-            for lod in 0..=settings::LODS.len() {
-                let terrain_lod = world_data.lods.get(lod).expect("Could not get LOD!");
-
-                if check_clip(direction, plane, &node.bounding_box)
-                    && plane::get_lod(eye, vec3(node.x, 0.0, node.z), camera.uniforms.data.z_far) == lod as u32
-                {
-                    if let Some(data) = &node.get_data() {
-                        let ct = plane::get_connect_type(eye, vec3(node.x, 0.0, node.z), lod as u32, camera.uniforms.data.z_far);
-                        let lod_buffer = terrain_lod.get(&ct).unwrap();
-
-                        encoder.set_bind_group(3, &data.uniforms.bind_group, &[]);
-                        encoder.set_index_buffer(lod_buffer.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                        encoder.draw_indexed(0..lod_buffer.length, 0, 0..1);
-                    }
-                }
-            }
-        }
+        self.draw_nodes(&mut encoder, world_data, view);
 
         encoder.finish(&wgpu::RenderBundleDescriptor { label: Some("terrain") })
     }
+
+    pub fn get_shadow_bundle(
+        &self,
+        device: &wgpu::Device,
+        camera: &camera::Instance,
+        world_data: &world::WorldData,
+        view: &culling::ViewCulling,
+        cascade: usize,
+    ) -> wgpu::RenderBundle {
+        optick::event!();
+        let mut encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+            label: Some("terrain_shadow_bundle"),
+            color_formats: &[],
+            depth_stencil_format: Some(settings::DEPTH_TEXTURE_FORMAT),
+            sample_count: 1,
+        });
+
+        // The shadow maps are the render target so the environment textures are left unbound
+        encoder.set_pipeline(&self.shadow_pipelines[cascade]);
+        encoder.set_bind_group(0, &camera.uniforms.bind_group, &[]);
+        encoder.set_bind_group(1, &self.texture_bind_group, &[]);
+        encoder.set_bind_group(2, &self.noise_bindings.bind_group, &[]);
+        encoder.set_bind_group(4, &world_data.environment.uniforms.bind_group, &[]);
+        encoder.set_bind_group(6, &world_data.map.textures.bind_group, &[]);
+        self.draw_nodes(&mut encoder, world_data, view);
+
+        encoder.finish(&wgpu::RenderBundleDescriptor {
+            label: Some("terrain_shadow"),
+        })
+    }
+
+    // Every LOD and connect type gets an indirect draw, the culling pass decides which nodes end up in them
+    fn draw_nodes<'a>(
+        &'a self,
+        encoder: &mut wgpu::RenderBundleEncoder<'a>,
+        world_data: &'a world::WorldData,
+        view: &'a culling::ViewCulling,
+    ) {
+        encoder.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for draw in world_data.culling.terrain_draws(false) {
+            let lod_buffer = world_data.lods[draw.lod].get(&draw.connect_type).unwrap();
+            encoder.set_bind_group(3, &view.render_bind_group, &[draw.visible_offset]);
+            encoder.set_index_buffer(lod_buffer.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            encoder.draw_indexed_indirect(&view.node_draws, draw.draw_offset);
+        }
+    }
 }
 
-fn check_clip(direction: f32, plane: f32, bounding_box: &camera::BoundingBox) -> bool {
-    direction == 0.0 || (direction > 0.0 && bounding_box.max.y >= plane) || (direction <= 0.0 && bounding_box.min.y < plane)
+// One pipeline per cascade as the depth bias is part of the pipeline state
+fn create_shadow_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shadows: &config::ShadowConfig,
+) -> Vec<wgpu::RenderPipeline> {
+    let module = device.create_shader_module(&wgpu::include_spirv!("../../../shaders/compiled/terrain-shadows.vert.spv"));
+    shadows
+        .depth_bias
+        .iter()
+        .map(|bias| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("terrain_shadow_pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: "main",
+                    buffers: &[plane::Vertex::desc()],
+                },
+                fragment: None,
+                primitive: wgpu::PrimitiveState {
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: wgpu::CullMode::Back,
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: settings::DEPTH_TEXTURE_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: bias.constant,
+                        slope_scale: bias.slope_scale,
+                        clamp: 0.0,
+                    },
+                    clamp_depth: true,
+                }),
+                multisample: wgpu::MultisampleState::default(),
+            })
+        })
+        .collect()
 }
 
 fn load_texture(device: &wgpu::Device, queue: &wgpu::Queue, path: &str) -> wgpu::TextureView {
@@ -190,26 +259,16 @@ fn load_texture(device: &wgpu::Device, queue: &wgpu::Queue, path: &str) -> wgpu:
     )
 }
 
-fn build_textures(device: &wgpu::Device, queue: &wgpu::Queue, texture_bind_group_layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+fn build_textures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture_bind_group_layout: &wgpu::BindGroupLayout,
+    biome_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
     let sampler = texture::create_sampler(device, wgpu::AddressMode::Repeat, wgpu::FilterMode::Linear);
 
     // TODO: Use 3D texture
-    let paths = vec![
-        "desert",
-        "grassland",
-        "snow",
-        "tropical",
-        "tundra",
-        "barren",
-        "rock",
-        "cliff",
-        "sand",
-        "flowers",
-        "mud",
-        "pebbles_grass",
-        "pebbles_desert",
-    ];
-    let textures = paths
+    let textures = map::biomes::TEXTURES
         .par_iter()
         .flat_map(|&t| {
             vec![
@@ -232,6 +291,10 @@ fn build_textures(device: &wgpu::Device, queue: &wgpu::Queue, texture_bind_group
                 binding: 1,
                 resource: wgpu::BindingResource::Sampler(&sampler),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: biome_buffer.as_entire_binding(),
+            },
         ],
     })
 }
@@ -1,13 +1,10 @@
 use crate::{
-    camera, noise, plane, settings, texture,
-    world::{self, node::Node},
+    camera, map, noise, plane, settings, texture,
+    world::{self, culling},
 };
-use cgmath::*;
-use std::collections::HashMap;
 use wgpu::util::DeviceExt;
 
 pub struct Water {
-    pub lods: Vec<HashMap<plane::ConnectType, plane::LodBuffer>>,
     pub render_pipeline: wgpu::RenderPipeline,
     pub noise_bindings: noise::NoiseBindings,
     pub refraction_texture_view: wgpu::TextureView,
@@ -16,7 +13,6 @@ pub struct Water {
     pub reflection_depth_texture_view: wgpu::TextureView,
     pub texture_bind_group: wgpu::BindGroup,
     pub vertex_buffer: wgpu::Buffer,
-    pub node_uniform_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl Water {
@@ -25,15 +21,11 @@ impl Water {
         viewport: &camera::Viewport,
         noise: &noise::Noise,
         tile: &plane::Plane,
+        map: &map::Map,
         env: &world::enivornment::Environment,
+        culling: &culling::Culling,
     ) -> Self {
         let noise_bindings = noise.create_bindings(device);
-        let mut lods = vec![];
-
-        for lod in 0..=settings::LODS.len() {
-            let indices_lod = tile.create_indices(&device, lod as u32 + 1);
-            lods.push(indices_lod);
-        }
 
         // Textures
         let sampler = texture::create_sampler(device, wgpu::AddressMode::ClampToEdge, wgpu::FilterMode::Nearest);
@@ -74,29 +66,16 @@ impl Water {
             ],
         });
 
-        let node_uniform_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("uniform_bind_group_layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("water_pipeline_layout"),
             bind_group_layouts: &[
                 &viewport.bind_group_layout,
                 &texture_bind_group_layout,
                 &noise_bindings.bind_group_layout,
-                &node_uniform_bind_group_layout,
+                &culling.render_bind_group_layout,
                 &env.uniform_bind_group_layout,
                 &env.texture_bind_group_layout,
+                &map.textures.bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
@@ -140,7 +119,6 @@ impl Water {
         });
 
         Self {
-            lods,
             render_pipeline,
             noise_bindings,
             reflection_texture_view,
@@ -149,7 +127,6 @@ impl Water {
             texture_bind_group,
             reflection_depth_texture_view,
             vertex_buffer,
-            node_uniform_bind_group_layout,
         }
     }
 
@@ -158,7 +135,7 @@ impl Water {
         device: &wgpu::Device,
         camera: &camera::Instance,
         world_data: &world::WorldData,
-        nodes: &Vec<&Node>,
+        view: &culling::ViewCulling,
     ) -> wgpu::RenderBundle {
         optick::event!();
         let mut encoder = device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
@@ -173,37 +150,17 @@ impl Water {
         encoder.set_bind_group(2, &self.noise_bindings.bind_group, &[]);
         encoder.set_bind_group(4, &world_data.environment.uniforms.bind_group, &[]);
         encoder.set_bind_group(5, &world_data.environment.texture_bind_group, &[]);
+        encoder.set_bind_group(6, &world_data.map.textures.bind_group, &[]);
         encoder.set_vertex_buffer(0, self.vertex_buffer.slice(..));
 
-        let plane = camera.uniforms.data.clip[3];
-        let eye = vec3(
-            camera.uniforms.data.eye_pos[0],
-            camera.uniforms.data.eye_pos[1],
-            camera.uniforms.data.eye_pos[2],
-        );
-
-        for node in nodes {
-            for lod in 0..=settings::LODS.len() {
-                let water_lod = world_data.lods.get(lod).expect("Could not get LOD!");
-
-                if check_clip(plane, node.bounding_box.min.y)
-                    && plane::get_lod(eye, vec3(node.x, 0.0, node.z), camera.uniforms.data.z_far) == lod as u32
-                {
-                    if let Some(data) = &node.get_data() {
-                        let ct = plane::get_connect_type(eye, vec3(node.x, 0.0, node.z), lod as u32, camera.uniforms.data.z_far);
-                        let lod_buffer = water_lod.get(&ct).unwrap();
-                        encoder.set_bind_group(3, &data.uniforms.bind_group, &[]);
-                        encoder.set_index_buffer(lod_buffer.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                        encoder.draw_indexed(0..lod_buffer.length, 0, 0..1);
-                    }
-                }
-            }
+        // Only nodes containing sea, lakes or rivers end up in the water draws
+        for draw in world_data.culling.terrain_draws(true) {
+            let lod_buffer = world_data.lods[draw.lod].get(&draw.connect_type).unwrap();
+            encoder.set_bind_group(3, &view.render_bind_group, &[draw.visible_offset]);
+            encoder.set_index_buffer(lod_buffer.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            encoder.draw_indexed_indirect(&view.node_draws, draw.draw_offset);
         }
 
         encoder.finish(&wgpu::RenderBundleDescriptor { label: Some("water") })
     }
 }
-
-fn check_clip(plane: f32, y_min: f32) -> bool {
-    y_min <= plane
-}
@@ -1,7 +1,7 @@
 use super::renderer;
 use crate::{
     camera,
-    world::{node, systems, WorldData},
+    world::{culling, WorldData},
 };
 use cgmath::*;
 
@@ -10,24 +10,21 @@ pub struct Eye {
     pub water_bundle: wgpu::RenderBundle,
     pub asset_bundle: wgpu::RenderBundle,
     pub sky_bundle: wgpu::RenderBundle,
-    pub asset_instances: systems::assets::InstanceBufferMap,
+    pub culling: culling::ViewCulling,
     pub camera: camera::Instance,
 }
 
 impl Eye {
-    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport, root_node: &node::Node) -> Self {
+    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport) -> Self {
         let camera = camera::Instance::from_controller(device, &viewport, [0.0, 1.0, 0.0, 1.0]);
-        let nodes = root_node.get_nodes(&camera.frustum);
-        let mut asset_instances = world_data.assets.get_instances(device);
+        let culling = culling::ViewCulling::new(device, &world_data.culling);
 
         Self {
-            terrain_bundle: world_data.terrain.get_bundle(device, &camera, &world_data, &nodes),
-            water_bundle: world_data.water.get_bundle(device, &camera, &world_data, &nodes),
+            terrain_bundle: world_data.terrain.get_bundle(device, &camera, &world_data, &culling),
+            water_bundle: world_data.water.get_bundle(device, &camera, &world_data, &culling),
             sky_bundle: world_data.sky.get_bundle(device, &camera),
-            asset_bundle: world_data
-                .assets
-                .get_bundle(device, viewport, &camera, world_data, &mut asset_instances, &nodes),
-            asset_instances,
+            asset_bundle: world_data.assets.get_bundle(device, &camera, world_data, &culling),
+            culling,
             camera,
         }
     }
@@ -39,9 +36,10 @@ impl Eye {
         world_data: &WorldData,
         viewport: &camera::Viewport,
         view: &Matrix4<f32>,
-        root_node: &node::Node,
     ) {
         optick::event!();
+        // Only the eye view has a depth buffer to occlude with, it was rendered with the matrix of the previous frame
+        let previous = Matrix4::from(self.camera.uniforms.data.view_proj);
         self.camera.update(
             queue,
             viewport.target,
@@ -50,21 +48,35 @@ impl Eye {
             viewport.z_near..viewport.z_far,
         );
 
-        let nodes = root_node.get_nodes(&self.camera.frustum);
-        self.terrain_bundle = world_data.terrain.get_bundle(device, &self.camera, &world_data, &nodes);
-        self.water_bundle = world_data.water.get_bundle(device, &self.camera, &world_data, &nodes);
-        self.asset_bundle = world_data
-            .assets
-            .get_bundle(device, viewport, &self.camera, &world_data, &mut self.asset_instances, &nodes);
+        self.culling.update(
+            device,
+            queue,
+            &world_data.culling,
+            &self.camera.frustum,
+            self.camera.uniforms.data.clip,
+            &world_data.environment,
+            Some(previous),
+        );
+        if self.culling.prepare(device, &world_data.culling) {
+            self.asset_bundle = world_data.assets.get_bundle(device, &self.camera, &world_data, &self.culling);
+        }
     }
 
     pub fn resize(&mut self, device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport) {
         self.camera.resize(viewport.width, viewport.height);
         self.sky_bundle = world_data.sky.get_bundle(device, &self.camera);
+        self.water_bundle = world_data.water.get_bundle(device, &self.camera, &world_data, &self.culling);
     }
 
-    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, color_target: &wgpu::TextureView, depth_target: &wgpu::TextureView) {
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        world_data: &WorldData,
+        color_target: &wgpu::TextureView,
+        depth_target: &wgpu::TextureView,
+    ) {
         optick::event!();
+        self.culling.cull(encoder, &world_data.culling);
         renderer::render(
             "sky",
             encoder,
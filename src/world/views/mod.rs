@@ -1,4 +1,4 @@
-use super::{node, WorldData};
+use super::WorldData;
 use crate::camera;
 use cgmath::*;
 mod eye;
@@ -15,12 +15,12 @@ pub struct Views {
 }
 
 impl Views {
-    pub fn new(device: &wgpu::Device, world: &WorldData, viewport: &camera::Viewport, root_node: &node::Node) -> Views {
+    pub fn new(device: &wgpu::Device, world: &WorldData, viewport: &camera::Viewport) -> Views {
         Self {
-            eye: eye::Eye::new(device, world, viewport, root_node),
-            refraction: refraction::Refraction::new(device, world, viewport, root_node),
-            reflection: reflection::Reflection::new(device, world, viewport, root_node),
-            shadow: shadow::Shadow::new(device, world, viewport, root_node),
+            eye: eye::Eye::new(device, world, viewport),
+            refraction: refraction::Refraction::new(device, world, viewport),
+            reflection: reflection::Reflection::new(device, world, viewport),
+            shadow: shadow::Shadow::new(device, world, viewport),
         }
     }
 
@@ -30,7 +30,6 @@ impl Views {
         queue: &wgpu::Queue,
         world: &WorldData,
         viewport: &camera::Viewport,
-        root_node: &node::Node,
         view: &Matrix4<f32>,
     ) {
         optick::event!();
@@ -41,16 +40,16 @@ impl Views {
             let shadow = &mut self.shadow;
 
             scope.spawn(move |_| {
-                eye.update(device, queue, world, viewport, view, root_node);
+                eye.update(device, queue, world, viewport, view);
             });
             scope.spawn(move |_| {
-                reflection.update(device, queue, world, viewport, root_node);
+                reflection.update(device, queue, world, viewport);
             });
             scope.spawn(move |_| {
-                refraction.update(device, queue, world, viewport, root_node);
+                refraction.update(device, queue, world, viewport);
             });
             scope.spawn(move |_| {
-                shadow.update(device, queue, world, viewport, view, root_node);
+                shadow.update(device, queue, world, viewport);
             });
         })
         .unwrap();
@@ -74,6 +73,6 @@ impl Views {
         self.shadow.render(encoder, world);
         self.reflection.render(encoder, world);
         self.refraction.render(encoder, world);
-        self.eye.render(encoder, color_target, depth_target);
+        self.eye.render(encoder, world, color_target, depth_target);
     }
 }
@@ -1,10 +1,7 @@
 use super::renderer;
 use crate::{
     camera,
-    world::{
-        node::{self},
-        systems, WorldData,
-    },
+    world::{culling, WorldData},
 };
 use cgmath::*;
 
@@ -12,36 +9,25 @@ pub struct Reflection {
     pub terrain_bundle: wgpu::RenderBundle,
     pub asset_bundle: wgpu::RenderBundle,
     pub sky_bundle: wgpu::RenderBundle,
-    pub asset_instances: systems::assets::InstanceBufferMap,
+    pub culling: culling::ViewCulling,
     pub camera: camera::Instance,
 }
 
 impl Reflection {
-    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport, root_node: &node::Node) -> Self {
+    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport) -> Self {
         let camera = camera::Instance::from_controller(device, &viewport, [0.0, 1.0, 0.0, 1.0]);
-
-        let nodes = root_node.get_nodes(&Box::new(camera.frustum));
-        let mut asset_instances = world_data.assets.get_instances(device);
+        let culling = culling::ViewCulling::new(device, &world_data.culling);
 
         Self {
-            terrain_bundle: world_data.terrain.get_bundle(device, &camera, &world_data, &nodes),
+            terrain_bundle: world_data.terrain.get_bundle(device, &camera, &world_data, &culling),
             sky_bundle: world_data.sky.get_bundle(device, &camera),
-            asset_bundle: world_data
-                .assets
-                .get_bundle(device, viewport, &camera, world_data, &mut asset_instances, &nodes),
-            asset_instances,
+            asset_bundle: world_data.assets.get_bundle(device, &camera, world_data, &culling),
+            culling,
             camera,
         }
     }
 
-    pub fn update(
-        &mut self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        world_data: &WorldData,
-        viewport: &camera::Viewport,
-        root_node: &node::Node,
-    ) {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world_data: &WorldData, viewport: &camera::Viewport) {
         optick::event!();
         let view = Matrix4::look_at_rh(
             Point3::new(viewport.eye.x, -viewport.eye.y, viewport.eye.z),
@@ -56,11 +42,18 @@ impl Reflection {
             viewport.z_near..viewport.z_far,
         );
 
-        let nodes = root_node.get_nodes(&Box::new(self.camera.frustum));
-        self.terrain_bundle = world_data.terrain.get_bundle(device, &self.camera, &world_data, &nodes);
-        self.asset_bundle = world_data
-            .assets
-            .get_bundle(device, viewport, &self.camera, &world_data, &mut self.asset_instances, &nodes);
+        self.culling.update(
+            device,
+            queue,
+            &world_data.culling,
+            &self.camera.frustum,
+            self.camera.uniforms.data.clip,
+            &world_data.environment,
+            None,
+        );
+        if self.culling.prepare(device, &world_data.culling) {
+            self.asset_bundle = world_data.assets.get_bundle(device, &self.camera, &world_data, &self.culling);
+        }
     }
 
     pub fn resize(&mut self, device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport) {
@@ -70,6 +63,7 @@ impl Reflection {
 
     pub fn render(&self, encoder: &mut wgpu::CommandEncoder, world_data: &WorldData) {
         optick::event!();
+        self.culling.cull(encoder, &world_data.culling);
         renderer::render(
             "sky",
             encoder,
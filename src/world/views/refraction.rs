@@ -1,34 +1,29 @@
 use super::renderer;
 use crate::{
     camera,
-    world::{node, WorldData},
+    world::{culling, WorldData},
 };
 use cgmath::*;
 
 pub struct Refraction {
     pub terrain_bundle: wgpu::RenderBundle,
+    pub culling: culling::ViewCulling,
     pub camera: camera::Instance,
 }
 
 impl Refraction {
-    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport, root_node: &node::Node) -> Self {
+    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport) -> Self {
         let camera = camera::Instance::from_controller(device, &viewport, [0.0, -1.0, 0.0, 1.0]);
-        let nodes = root_node.get_nodes(&Box::new(camera.frustum));
+        let culling = culling::ViewCulling::new(device, &world_data.culling);
 
         Self {
-            terrain_bundle: world_data.terrain.get_bundle(device, &camera, &world_data, &nodes),
+            terrain_bundle: world_data.terrain.get_bundle(device, &camera, &world_data, &culling),
+            culling,
             camera,
         }
     }
 
-    pub fn update(
-        &mut self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        world_data: &WorldData,
-        viewport: &camera::Viewport,
-        root_node: &node::Node,
-    ) {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world_data: &WorldData, viewport: &camera::Viewport) {
         optick::event!();
         let view = Matrix4::look_at_rh(viewport.eye, viewport.target, Vector3::unit_y());
         self.camera.update(
@@ -38,9 +33,15 @@ impl Refraction {
             viewport.proj * view,
             viewport.z_near..viewport.z_far,
         );
-
-        let nodes = root_node.get_nodes(&Box::new(self.camera.frustum));
-        self.terrain_bundle = world_data.terrain.get_bundle(device, &self.camera, &world_data, &nodes);
+        self.culling.update(
+            device,
+            queue,
+            &world_data.culling,
+            &self.camera.frustum,
+            self.camera.uniforms.data.clip,
+            &world_data.environment,
+            None,
+        );
     }
 
     pub fn resize(&mut self, viewport: &camera::Viewport) {
@@ -49,6 +50,7 @@ impl Refraction {
 
     pub fn render(&self, encoder: &mut wgpu::CommandEncoder, world_data: &WorldData) {
         optick::event!();
+        self.culling.cull(encoder, &world_data.culling);
         renderer::render(
             "environment",
             encoder,
@@ -1,29 +1,27 @@
 use super::renderer;
 use crate::{
-    camera, settings,
-    world::{node, systems, WorldData},
+    camera, logger,
+    world::{culling, WorldData},
 };
-use cgmath::*;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
 pub struct Cascade {
-    pub asset_instances: systems::assets::InstanceBufferMap,
+    pub culling: culling::ViewCulling,
+    pub terrain_bundle: wgpu::RenderBundle,
     pub asset_bundle: wgpu::RenderBundle,
     pub camera: camera::Instance,
     pub i: usize,
 }
 
 impl Cascade {
-    fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport, root_node: &node::Node, i: usize) -> Self {
+    fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport, i: usize) -> Self {
         let camera = camera::Instance::from_controller(device, &viewport, [0.0, 1.0, 0.0, 1.0]);
-        let nodes = root_node.get_nodes(&Box::new(camera.frustum));
-        let mut asset_instances = world_data.assets.get_instances(device);
+        let culling = culling::ViewCulling::new(device, &world_data.culling);
 
         Self {
-            asset_bundle: world_data
-                .assets
-                .get_shadow_bundle(device, viewport, &camera, world_data, &mut asset_instances, &nodes),
-            asset_instances,
+            terrain_bundle: world_data.terrain.get_shadow_bundle(device, &camera, world_data, &culling, i),
+            asset_bundle: world_data.assets.get_shadow_bundle(device, &camera, world_data, &culling, i),
+            culling,
             camera,
             i,
         }
@@ -35,27 +33,18 @@ pub struct Shadow {
 }
 
 impl Shadow {
-    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport, root_node: &node::Node) -> Self {
+    pub fn new(device: &wgpu::Device, world_data: &WorldData, viewport: &camera::Viewport) -> Self {
         Self {
-            cascades: (0..settings::SHADOW_CASCADE_SPLITS.len())
-                .map(|i| Cascade::new(device, world_data, viewport, root_node, i))
+            cascades: (0..world_data.environment.shadow_matrix.len())
+                .map(|i| Cascade::new(device, world_data, viewport, i))
                 .collect(),
         }
     }
 
-    pub fn update(
-        &mut self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        world_data: &WorldData,
-        viewport: &camera::Viewport,
-        view: &Matrix4<f32>,
-        root_node: &node::Node,
-    ) {
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world_data: &WorldData, viewport: &camera::Viewport) {
         optick::event!();
-        self.cascades.par_iter_mut().for_each(|c| {
-            let nodes = root_node.get_nodes(&Box::new(c.camera.frustum));
-
+        let updated = &world_data.environment.shadow_updated;
+        self.cascades.par_iter_mut().filter(|c| updated[c.i]).for_each(|c| {
             c.camera.update(
                 queue,
                 viewport.target,
@@ -63,29 +52,44 @@ impl Shadow {
                 world_data.environment.shadow_matrix[c.i],
                 viewport.z_near..viewport.z_far,
             );
-            c.camera.frustum = camera::FrustumCuller::from_matrix(viewport.proj * view);
-            c.asset_bundle = world_data
-                .assets
-                .get_shadow_bundle(device, viewport, &c.camera, world_data, &mut c.asset_instances, &nodes);
+            // Casters outside the eye frustum still shadow what is inside it so each cascade culls against the light's box,
+            // the shadow pipelines clamp depth so casters between the light and the box are kept as well
+            c.camera.frustum = camera::FrustumCuller::from_matrix(world_data.environment.shadow_matrix[c.i]).without_near();
+            c.culling.update(
+                device,
+                queue,
+                &world_data.culling,
+                &c.camera.frustum,
+                c.camera.uniforms.data.clip,
+                &world_data.environment,
+                None,
+            );
+            if c.culling.prepare(device, &world_data.culling) {
+                c.asset_bundle = world_data.assets.get_shadow_bundle(device, &c.camera, world_data, &c.culling, c.i);
+            }
         });
     }
 
     pub fn resize(&mut self, viewport: &camera::Viewport) {
-        for i in 0..settings::SHADOW_CASCADE_SPLITS.len() {
-            self.cascades[i].camera.resize(viewport.width, viewport.height);
+        for cascade in self.cascades.iter_mut() {
+            cascade.camera.resize(viewport.width, viewport.height);
         }
     }
 
     pub fn render(&self, encoder: &mut wgpu::CommandEncoder, world_data: &WorldData) {
         optick::event!();
-        for i in 0..settings::SHADOW_CASCADE_SPLITS.len() {
+        // Cascades that were not moved this frame keep what they rendered before
+        let cascades = self.cascades.iter().filter(|c| world_data.environment.shadow_updated[c.i]);
+        logger::count("Shadows: cascades rendered", cascades.clone().count() as u64);
+        for cascade in cascades {
+            cascade.culling.cull(encoder, &world_data.culling);
             renderer::render(
                 "shadows",
                 encoder,
                 renderer::Args {
-                    bundles: vec![&self.cascades[i].asset_bundle],
+                    bundles: vec![&cascade.terrain_bundle, &cascade.asset_bundle],
                     color_targets: &[],
-                    depth_target: Some(&world_data.environment.shadow_texture_view[i]),
+                    depth_target: Some(&world_data.environment.shadow_texture_view[cascade.i]),
                     clear_color: false,
                     clear_depth: true,
                 },